@@ -1,4 +1,4 @@
-use a0kzg::{Kzg, Scalar};
+use a0kzg::{Kzg, Poly, Scalar};
 use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
 use once_cell::sync::Lazy;
 use rand::Rng;
@@ -21,6 +21,25 @@ static POINTS: Lazy<Vec<(Scalar, Scalar)>> = Lazy::new(|| {
         .collect::<Vec<_>>()
 });
 
+static ZKG_4096: Lazy<Kzg> = Lazy::new(|| Kzg::trusted_setup(4096));
+
+fn zkg_commit(c: &mut Criterion) {
+    let mut group = c.benchmark_group("zkg_commit");
+    let mut rng = rand::thread_rng();
+    for size in [256usize, 1024, 4096].iter() {
+        let poly = Poly::new(
+            (0..*size)
+                .map(|_| Scalar::from_raw([rng.gen(), rng.gen(), rng.gen(), rng.gen()]))
+                .collect(),
+        );
+        group.throughput(Throughput::Elements(*size as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), size, |b, _| {
+            b.iter(|| ZKG_4096.commit(&poly).unwrap())
+        });
+    }
+    group.finish();
+}
+
 fn zkg_prover(c: &mut Criterion) {
     let mut group = c.benchmark_group("zkg_prover");
     for zkg in ZKGS.iter() {
@@ -48,5 +67,5 @@ fn zkg_verifier(c: &mut Criterion) {
     group.finish();
 }
 
-criterion_group!(benches, zkg_prover, zkg_verifier);
+criterion_group!(benches, zkg_commit, zkg_prover, zkg_verifier);
 criterion_main!(benches);
@@ -0,0 +1,418 @@
+//! This module implements the Bandersnatch curve, a twisted Edwards curve
+//! -5·x² + y² = 1 + d·x²·y² over the bls12_381 scalar field, so its arithmetic can be
+//! proved efficiently inside bls12_381 SNARKs
+//! see https://eprint.iacr.org/2021/1152.pdf
+//!
+//! The curve has order 4·r, points are taken modulo the 2-torsion point (0, -1) as in
+//! the Banderwagon group that Ethereum uses for Verkle trees, so (x, y) and (-x, -y)
+//! are the same point, the group has prime order r and each point is encoded with its
+//! x coordinate only
+//! see https://hackmd.io/@6iQDuIePQjyYBqDChYw_jg/BJ2-L6Nzc
+
+use super::msm::{pippenger, MsmGroup};
+use bls12_381::Scalar;
+use ff::{Field, PrimeField};
+use sha2::{Digest, Sha256};
+use std::ops::{Add, AddAssign, Mul, Neg, Sub};
+
+/// The scalar field of the Bandersnatch prime order subgroup
+#[derive(PrimeField)]
+#[PrimeFieldModulus = "13108968793781547619861935127046491459309155893440570251786403306729687672801"]
+#[PrimeFieldGenerator = "7"]
+#[PrimeFieldReprEndianness = "little"]
+pub struct BandersnatchScalar([u64; 4]);
+
+/// Seed of the hash-to-curve of the Pedersen basis, as specified for Ethereum
+pub const PEDERSEN_BASIS_SEED: &[u8] = b"eth_verkle_oct_2021";
+
+/// The d coefficient, 138827208126141220649022263972958607803/171449701953573178309673572579671231137
+const D: Scalar = Scalar::from_raw([
+    0xb369_f2f5_188d_58e7,
+    0xcb66_6771_77e5_4f92,
+    0xc66e_3bf8_6be3_b6d8,
+    0x6389_c126_33c2_67cb,
+]);
+
+const GENERATOR_X: Scalar = Scalar::from_raw([
+    0xe1e7_1866_a252_ae18,
+    0x2b79_c022_ad99_8465,
+    0x7437_1177_7bbe_42f3,
+    0x29c1_32cc_2c0b_34c5,
+]);
+
+const GENERATOR_Y: Scalar = Scalar::from_raw([
+    0x5e31_67b6_cc97_4166,
+    0x358c_ad81_eee4_6460,
+    0x157d_8b50_badc_d586,
+    0x2a6c_669e_da12_3e0f,
+]);
+
+impl BandersnatchScalar {
+    /// Interprets `bytes` as a little-endian integer reduced modulo r
+    pub fn from_le_bytes_mod_order(bytes: &[u8]) -> Self {
+        // 2^128 chunks always fit in the field, so they are added from the top
+        let shift = Self::from_u128(u128::MAX) + Self::ONE;
+        let mut acc = Self::ZERO;
+        for chunk in bytes.chunks(16).rev() {
+            let mut limb = [0u8; 16];
+            limb[..chunk.len()].copy_from_slice(chunk);
+            acc = acc * shift + Self::from_u128(u128::from_le_bytes(limb));
+        }
+        acc
+    }
+
+    /// The scalar as a bls12_381 scalar with the same integer value, r is smaller than
+    /// the bls12_381 modulus so it always fits
+    pub(crate) fn to_bls_scalar(self) -> Scalar {
+        Scalar::from_bytes(&self.to_repr().0).unwrap()
+    }
+}
+
+/// A point of the Bandersnatch curve modulo the 2-torsion, in extended coordinates
+/// (X:Y:T:Z) with x=X/Z, y=Y/Z and x·y=T/Z
+#[derive(Clone, Copy, Debug)]
+pub struct BandersnatchPoint {
+    x: Scalar,
+    y: Scalar,
+    t: Scalar,
+    z: Scalar,
+}
+
+impl BandersnatchPoint {
+    /// The neutral element (0, 1)
+    pub fn identity() -> Self {
+        Self {
+            x: Scalar::zero(),
+            y: Scalar::one(),
+            t: Scalar::zero(),
+            z: Scalar::one(),
+        }
+    }
+
+    /// The generator of the prime order subgroup
+    pub fn generator() -> Self {
+        Self::from_affine(GENERATOR_X, GENERATOR_Y)
+    }
+
+    fn from_affine(x: Scalar, y: Scalar) -> Self {
+        Self {
+            x,
+            y,
+            t: x * y,
+            z: Scalar::one(),
+        }
+    }
+
+    /// Returns the affine coordinates (x, y)
+    pub fn to_affine(&self) -> (Scalar, Scalar) {
+        let z_inv = self.z.invert().unwrap();
+        (self.x * z_inv, self.y * z_inv)
+    }
+
+    /// Returns if the point is the identity, both (0, 1) and (0, -1)
+    pub fn is_identity(&self) -> bool {
+        self.x == Scalar::zero()
+    }
+
+    /// Returns 2·self
+    pub fn double(&self) -> Self {
+        *self + *self
+    }
+
+    /// Finds the point with x coordinate `x` and the lexicographically largest y,
+    /// returns None if there is no such point in the prime order subgroup
+    fn from_x(x: Scalar) -> Option<Self> {
+        // x is in the subgroup iff 1 - a·x² is a square
+        let x2 = x.square();
+        let num = Scalar::one() + Scalar::from(5) * x2;
+        if bool::from(num.sqrt().is_none()) {
+            return None;
+        }
+        let den = Option::<Scalar>::from((Scalar::one() - D * x2).invert())?;
+        let y = Option::<Scalar>::from((num * den).sqrt())?;
+        let y = if is_lexicographically_largest(&y) {
+            y
+        } else {
+            -y
+        };
+        Some(Self::from_affine(x, y))
+    }
+
+    /// Encodes the point as the big-endian x coordinate of the representative with the
+    /// lexicographically largest y
+    /// # Example
+    /// ```
+    /// use a0kzg::BandersnatchPoint;
+    /// let g = BandersnatchPoint::generator();
+    /// assert_eq!(BandersnatchPoint::from_bytes(&g.to_bytes()), Some(g));
+    /// assert_eq!(BandersnatchPoint::from_bytes(&[0xff; 32]), None);
+    /// ```
+    pub fn to_bytes(&self) -> [u8; 32] {
+        let (x, y) = self.to_affine();
+        let x = if is_lexicographically_largest(&y) {
+            x
+        } else {
+            -x
+        };
+        let mut bytes = x.to_bytes();
+        bytes.reverse();
+        bytes
+    }
+
+    /// Decodes a point encoded with `to_bytes`, failing if x is not canonical or the
+    /// point is not in the prime order subgroup
+    pub fn from_bytes(bytes: &[u8; 32]) -> Option<Self> {
+        let mut le = *bytes;
+        le.reverse();
+        Self::from_x(Option::from(Scalar::from_bytes(&le))?)
+    }
+
+    /// Maps the point to a scalar as x/y, that is the same for both representatives,
+    /// reduced modulo r. The identity maps to zero
+    pub fn map_to_field(&self) -> BandersnatchScalar {
+        let (x, y) = self.to_affine();
+        BandersnatchScalar::from_le_bytes_mod_order(&(x * y.invert().unwrap()).to_bytes())
+    }
+}
+
+/// Returns if y > (p-1)/2, comparing the canonical big-endian encodings
+fn is_lexicographically_largest(y: &Scalar) -> bool {
+    let half = -Scalar::from(2).invert().unwrap();
+    let (mut y, mut half) = (y.to_bytes(), half.to_bytes());
+    y.reverse();
+    half.reverse();
+    y > half
+}
+
+/// Generates `n` points with unknown discrete logarithms between them, hashing
+/// `PEDERSEN_BASIS_SEED` with a big-endian counter into x coordinates and skipping
+/// the ones that are not in the prime order subgroup
+/// # Example
+/// ```
+/// use a0kzg::pedersen_basis;
+/// let basis = pedersen_basis(2);
+/// assert_eq!(
+///     hex::encode(basis[0].to_bytes()),
+///     "01587ad1336675eb912550ec2a28eb8923b824b490dd2ba82e48f14590a298a0"
+/// );
+/// ```
+pub fn pedersen_basis(n: usize) -> Vec<BandersnatchPoint> {
+    (0u64..)
+        .filter_map(|i| {
+            let mut hasher = Sha256::new();
+            hasher.update(PEDERSEN_BASIS_SEED);
+            hasher.update(i.to_be_bytes());
+            let mut bytes = [0u8; 64];
+            bytes[..32].copy_from_slice(&hasher.finalize());
+            bytes[..32].reverse();
+            BandersnatchPoint::from_x(Scalar::from_bytes_wide(&bytes))
+        })
+        .take(n)
+        .collect()
+}
+
+/// Computes Σ scalars_i·bases_i, extra bases or scalars are ignored
+pub fn msm_bandersnatch(
+    bases: &[BandersnatchPoint],
+    scalars: &[BandersnatchScalar],
+) -> BandersnatchPoint {
+    let scalars: Vec<Scalar> = scalars.iter().map(|s| s.to_bls_scalar()).collect();
+    pippenger(bases, &scalars)
+}
+
+impl Default for BandersnatchPoint {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl PartialEq for BandersnatchPoint {
+    fn eq(&self, other: &Self) -> bool {
+        // x1/y1 = x2/y2 identifies (x, y) with (-x, -y)
+        self.x * other.y == other.x * self.y
+    }
+}
+
+impl Eq for BandersnatchPoint {}
+
+impl Add for BandersnatchPoint {
+    type Output = BandersnatchPoint;
+
+    /// Unified addition in extended coordinates (add-2008-hwcd), also valid to double
+    fn add(self, other: BandersnatchPoint) -> BandersnatchPoint {
+        let a = self.x * other.x;
+        let b = self.y * other.y;
+        let c = D * self.t * other.t;
+        let d = self.z * other.z;
+        let e = (self.x + self.y) * (other.x + other.y) - a - b;
+        let f = d - c;
+        let g = d + c;
+        let h = b + Scalar::from(5) * a;
+        BandersnatchPoint {
+            x: e * f,
+            y: g * h,
+            t: e * h,
+            z: f * g,
+        }
+    }
+}
+
+impl<'a> AddAssign<&'a BandersnatchPoint> for BandersnatchPoint {
+    fn add_assign(&mut self, other: &'a BandersnatchPoint) {
+        *self = *self + *other;
+    }
+}
+
+impl AddAssign for BandersnatchPoint {
+    fn add_assign(&mut self, other: BandersnatchPoint) {
+        *self = *self + other;
+    }
+}
+
+impl Neg for BandersnatchPoint {
+    type Output = BandersnatchPoint;
+    fn neg(self) -> BandersnatchPoint {
+        BandersnatchPoint {
+            x: -self.x,
+            t: -self.t,
+            ..self
+        }
+    }
+}
+
+impl Sub for BandersnatchPoint {
+    type Output = BandersnatchPoint;
+    fn sub(self, other: BandersnatchPoint) -> BandersnatchPoint {
+        self + -other
+    }
+}
+
+impl<'a> Mul<&'a BandersnatchScalar> for BandersnatchPoint {
+    type Output = BandersnatchPoint;
+    fn mul(self, k: &'a BandersnatchScalar) -> BandersnatchPoint {
+        let mut acc = BandersnatchPoint::identity();
+        for byte in k.to_repr().0.iter().rev() {
+            for bit in (0..8).rev() {
+                acc = acc.double();
+                if (byte >> bit) & 1 == 1 {
+                    acc += &self;
+                }
+            }
+        }
+        acc
+    }
+}
+
+impl Mul<BandersnatchScalar> for BandersnatchPoint {
+    type Output = BandersnatchPoint;
+    fn mul(self, k: BandersnatchScalar) -> BandersnatchPoint {
+        self * &k
+    }
+}
+
+impl MsmGroup for BandersnatchPoint {
+    type Affine = BandersnatchPoint;
+    fn double(&self) -> Self {
+        BandersnatchPoint::double(self)
+    }
+}
+
+#[test]
+fn test_bandersnatch_arithmetic() {
+    let g = BandersnatchPoint::generator();
+    let o = BandersnatchPoint::identity();
+    let (x, y) = g.to_affine();
+    assert_eq!(
+        -Scalar::from(5) * x.square() + y.square(),
+        Scalar::one() + D * x.square() * y.square()
+    );
+
+    let k = |n: u64| BandersnatchScalar::from(n);
+    assert_eq!(g + o, g);
+    assert_eq!(g - g, o);
+    assert!((g - g).is_identity());
+    assert_eq!(g.double(), g * k(2));
+    assert_eq!(g * k(3) + g * k(4), g * k(7));
+    assert_eq!(g * -k(1), -g);
+    assert_ne!(g * k(2), g);
+    // the group has order r
+    assert_eq!(g * (BandersnatchScalar::ZERO - k(1)), -g);
+    assert!((g * (BandersnatchScalar::ZERO - k(1)) + g).is_identity());
+    // the 2-torsion point is the identity
+    assert_eq!(BandersnatchPoint::from_affine(-x, -y), g);
+    assert_eq!(
+        BandersnatchPoint::from_affine(Scalar::zero(), -Scalar::one()),
+        o
+    );
+
+    // little-endian bytes of any length, reduced modulo r
+    let from_bytes = BandersnatchScalar::from_le_bytes_mod_order;
+    assert_eq!(from_bytes(&[]), k(0));
+    assert_eq!(from_bytes(&[1, 2, 3]), k(0x030201));
+    let mut bytes = [0u8; 17];
+    bytes[0] = 5;
+    bytes[16] = 1;
+    assert_eq!(from_bytes(&bytes), from_bytes(&[0xff; 16]) + k(6));
+    assert_eq!(
+        from_bytes(&[0xff; 64]),
+        from_bytes(&[0xff; 32]) * from_bytes(&[0xff; 32])
+            + from_bytes(&[0xff; 32])
+            + from_bytes(&[0xff; 32])
+    );
+
+    let bases = [g, g * k(5), g * k(11)];
+    assert_eq!(msm_bandersnatch(&bases, &[k(1), k(2), k(3)]), g * k(44));
+}
+
+#[test]
+fn test_bandersnatch_serialization() {
+    let g = BandersnatchPoint::generator();
+    assert_eq!(
+        hex::encode(g.to_bytes()),
+        "4a2c7486fd924882bf02c6908de395122843e3e05264d7991e18e7985dad51e9"
+    );
+    assert_eq!(BandersnatchPoint::identity().to_bytes(), [0; 32]);
+    for n in 0..8u64 {
+        let p = g * BandersnatchScalar::from(n * 1000 + 1);
+        let q = BandersnatchPoint::from_bytes(&p.to_bytes()).unwrap();
+        assert_eq!(p, q);
+        assert_ne!((-p).to_bytes(), p.to_bytes());
+        assert_eq!(BandersnatchPoint::from_bytes(&(-p).to_bytes()), Some(-p));
+        assert_eq!(p.map_to_field(), q.map_to_field());
+        let (x, y) = p.to_affine();
+        assert_eq!(
+            BandersnatchPoint::from_affine(-x, -y).to_bytes(),
+            p.to_bytes()
+        );
+    }
+    assert_eq!(
+        BandersnatchPoint::identity().map_to_field(),
+        BandersnatchScalar::ZERO
+    );
+    // non canonical and out of the subgroup x coordinates are rejected
+    assert_eq!(BandersnatchPoint::from_bytes(&[0xff; 32]), None);
+    let mut not_in_subgroup = (1u64..)
+        .map(Scalar::from)
+        .find(|x| BandersnatchPoint::from_x(*x).is_none())
+        .unwrap()
+        .to_bytes();
+    not_in_subgroup.reverse();
+    assert_eq!(BandersnatchPoint::from_bytes(&not_in_subgroup), None);
+}
+
+#[test]
+fn test_pedersen_basis() {
+    let basis = pedersen_basis(256);
+    assert_eq!(
+        hex::encode(basis[1].to_bytes()),
+        "6c6e607df0723edfff382fa914bfc38136f3300ab2e06fb97007b559fd323b82"
+    );
+    let mut hasher = Sha256::new();
+    for p in basis.iter() {
+        hasher.update(p.to_bytes());
+    }
+    assert_eq!(
+        hex::encode(hasher.finalize()),
+        "1fcaea10bf24f750200e06fa473c76ff0468007291fa548e2d99f09ba9256fdb"
+    );
+}
@@ -0,0 +1,130 @@
+//! This module provides degree bound enforcement with shifted commitments, as in
+//! Sonic and Marlin. Being D the maximum degree of the setup, a polinomial p of
+//! degree at most d is also committed as [tau^(D-d)·p(tau)]₁, which is only possible
+//! if deg(p) <= d since the setup has no powers of tau beyond D
+//! see https://eprint.iacr.org/2019/1047.pdf (appendix B)
+
+use super::{
+    kzg::{Commitment, Kzg, KzgError},
+    msm::msm_g1,
+    poly::Poly,
+};
+use bls12_381::{pairing, G2Affine};
+
+impl Kzg {
+    /// Commits to `poly` and to x^(D-d)·poly, failing if its degree is larger than
+    /// `degree_bound` or the bound is larger than the setup. The verification needs
+    /// [tau^(D-d)]₂, so the bound is also rejected if the setup does not have it, for
+    /// example the Ethereum setup only has 65 powers of tau in G2
+    /// # Example
+    /// ```
+    /// use a0kzg::{Kzg, KzgError, Poly};
+    /// let kzg = Kzg::trusted_setup(8);
+    /// let p = Poly::from(&[1, 2, 3]);
+    /// let (c, shifted) = kzg.commit_with_degree_bound(&p, 2).unwrap();
+    /// assert_eq!(c, kzg.commit(&p).unwrap());
+    /// assert!(kzg.verify_degree_bound(&c, &shifted, 2));
+    /// assert!(!kzg.verify_degree_bound(&c, &shifted, 1));
+    /// assert_eq!(
+    ///     kzg.commit_with_degree_bound(&p, 1),
+    ///     Err(KzgError::DegreeTooLarge { degree: 2, max: 1 })
+    /// );
+    /// ```
+    pub fn commit_with_degree_bound(
+        &self,
+        poly: &Poly,
+        degree_bound: usize,
+    ) -> Result<(Commitment, Commitment), KzgError> {
+        let max_degree = self.max_degree();
+        if degree_bound > max_degree {
+            return Err(KzgError::DegreeTooLarge {
+                degree: degree_bound,
+                max: max_degree,
+            });
+        }
+        if self.pow_tau_g2.len() <= max_degree - degree_bound {
+            return Err(KzgError::DegreeBoundTooSmall {
+                degree_bound,
+                min: (max_degree + 1).saturating_sub(self.pow_tau_g2.len()),
+            });
+        }
+        if poly.degree() > degree_bound {
+            return Err(KzgError::DegreeTooLarge {
+                degree: poly.degree(),
+                max: degree_bound,
+            });
+        }
+        let commitment = self.commit(poly)?;
+        let shifted = msm_g1(&self.pow_tau_g1[max_degree - degree_bound..], &poly.0);
+        Ok((commitment, shifted))
+    }
+
+    /// Verifies that `shifted` is the commitment of x^(D-d)·p for the polinomial p
+    /// committed in `commitment`, so deg(p) <= `degree_bound`, by checking
+    /// e(shifted, [1]₂) = e(commitment, [tau^(D-d)]₂). It is false if the setup has
+    /// not [tau^(D-d)]₂, that are the bounds rejected by `commit_with_degree_bound`
+    pub fn verify_degree_bound(
+        &self,
+        commitment: &Commitment,
+        shifted: &Commitment,
+        degree_bound: usize,
+    ) -> bool {
+        let shift = match self.max_degree().checked_sub(degree_bound) {
+            Some(shift) => shift,
+            None => return false,
+        };
+        let tau_shift_g2 = match self.pow_tau_g2.get(shift) {
+            Some(tau_shift_g2) => tau_shift_g2,
+            None => return false,
+        };
+        pairing(&shifted.into(), &G2Affine::generator())
+            == pairing(&commitment.into(), tau_shift_g2)
+    }
+}
+
+#[test]
+fn test_degree_bound() {
+    let kzg = Kzg::trusted_setup(16);
+    let p = Poly::from(&[4, 8, 15, 16, 23, 42]);
+    let c = kzg.commit(&p).unwrap();
+
+    for d in [5usize, 6, 10, 15].iter() {
+        let (commitment, shifted) = kzg.commit_with_degree_bound(&p, *d).unwrap();
+        assert_eq!(commitment, c);
+        assert!(kzg.verify_degree_bound(&c, &shifted, *d));
+        // the shifted commitment is only valid for its own bound
+        assert!(!kzg.verify_degree_bound(&c, &shifted, d - 1));
+    }
+    assert!(!kzg.verify_degree_bound(&c, &c, 16));
+
+    // a prover can not build the shifted commitment of a larger degree polinomial
+    // with the setup, x^(D-4)·p has degree D+1
+    assert_eq!(
+        kzg.commit_with_degree_bound(&p, 4),
+        Err(KzgError::DegreeTooLarge { degree: 5, max: 4 })
+    );
+    assert_eq!(
+        kzg.commit_with_degree_bound(&p, 16),
+        Err(KzgError::DegreeTooLarge {
+            degree: 16,
+            max: 15
+        })
+    );
+
+    // with only [1]₂ and [tau]₂ the bounds below D-1 can not be verified
+    let kzg = Kzg {
+        pow_tau_g1: kzg.pow_tau_g1.clone(),
+        pow_tau_g2: kzg.pow_tau_g2[..2].to_vec(),
+        lagrange_g1: Vec::new(),
+        pow_gamma_tau_g1: Vec::new(),
+    };
+    assert_eq!(
+        kzg.commit_with_degree_bound(&p, 10),
+        Err(KzgError::DegreeBoundTooSmall {
+            degree_bound: 10,
+            min: 14
+        })
+    );
+    let (commitment, shifted) = kzg.commit_with_degree_bound(&p, 14).unwrap();
+    assert!(kzg.verify_degree_bound(&commitment, &shifted, 14));
+}
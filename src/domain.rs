@@ -0,0 +1,296 @@
+//! This module provides radix-2 evaluation domains over bls12_381::Scalar, used to
+//! move polinomials between coefficient and evaluation form with the NTT
+
+use super::poly::Poly;
+use bls12_381::{G1Projective, Scalar};
+use std::ops::{Add, Mul, Sub};
+
+/// Log2 of the largest power of two that divides r-1 in the bls12_381 scalar field
+pub const TWO_ADICITY: u32 = 32;
+
+/// 7^((r-1)/2^32), a primitive 2^32-th root of unity of the bls12_381 scalar field
+pub const ROOT_OF_UNITY: Scalar = Scalar::from_raw([
+    0x3829_971f_439f_0d2b,
+    0xb636_8350_8c22_80b9,
+    0xd09b_6819_22c8_13b4,
+    0x16a2_a19e_dfe8_1f20,
+]);
+
+/// A multiplicative subgroup of size 2^k of the scalar field, that is, the set
+/// {1, w, w^2, ... w^(n-1)} where w is a primitive n-th root of unity
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EvaluationDomain {
+    size: usize,
+    log_size: u32,
+    omega: Scalar,
+    omega_inv: Scalar,
+    size_inv: Scalar,
+}
+
+impl EvaluationDomain {
+    /// Creates the smallest domain that is able to hold `n` elements, returns None if
+    /// there is no subgroup of this size (n > 2^32)
+    /// # Examples
+    /// ```
+    ///    use a0kzg::EvaluationDomain;
+    ///    assert_eq!(EvaluationDomain::new(5).unwrap().size(), 8);
+    /// ```
+    pub fn new(n: usize) -> Option<Self> {
+        let size = n.max(1).checked_next_power_of_two()?;
+        let log_size = size.trailing_zeros();
+        if log_size > TWO_ADICITY {
+            return None;
+        }
+
+        let mut omega = ROOT_OF_UNITY;
+        for _ in log_size..TWO_ADICITY {
+            omega = omega.square();
+        }
+
+        Some(Self {
+            size,
+            log_size,
+            omega,
+            omega_inv: omega.invert().unwrap(),
+            size_inv: Scalar::from(size as u64).invert().unwrap(),
+        })
+    }
+
+    /// Returns the number of elements of the domain
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Returns log2 of the number of elements of the domain
+    pub fn log_size(&self) -> u32 {
+        self.log_size
+    }
+
+    /// Returns the generator of the domain, a primitive size-th root of unity
+    pub fn omega(&self) -> Scalar {
+        self.omega
+    }
+
+    /// Returns the inverse of the generator of the domain
+    pub fn omega_inv(&self) -> Scalar {
+        self.omega_inv
+    }
+
+    /// Returns the `i`-th element of the domain, w^i
+    pub fn element(&self, i: usize) -> Scalar {
+        self.omega.pow_vartime(&[i as u64, 0, 0, 0])
+    }
+
+    /// Returns all the elements of the domain, in order
+    pub fn elements(&self) -> Vec<Scalar> {
+        powers(self.omega, self.size)
+    }
+
+    /// Computes in place the evaluations of the polinomial with coefficients `a`
+    /// over the domain, `a` must have exactly size() elements
+    pub fn fft_in_place(&self, a: &mut [Scalar]) {
+        assert_eq!(a.len(), self.size);
+        serial_fft(a, self.omega, self.log_size);
+    }
+
+    /// Computes in place the coefficients of the polinomial that has `a` as
+    /// evaluations over the domain, `a` must have exactly size() elements
+    pub fn ifft_in_place(&self, a: &mut [Scalar]) {
+        assert_eq!(a.len(), self.size);
+        serial_fft(a, self.omega_inv, self.log_size);
+        a.iter_mut().for_each(|v| *v *= self.size_inv);
+    }
+
+    /// Like `fft_in_place` but over G1, computes [p(w^i)]₁ from [p_i]₁ where p_i
+    /// are the coefficients of p
+    pub fn fft_g1(&self, a: &mut [G1Projective]) {
+        assert_eq!(a.len(), self.size);
+        serial_fft(a, self.omega, self.log_size);
+    }
+
+    /// Like `ifft_in_place` but over G1, computes [p_i]₁ from [p(w^i)]₁ where p_i
+    /// are the coefficients of p
+    pub fn ifft_g1(&self, a: &mut [G1Projective]) {
+        assert_eq!(a.len(), self.size);
+        serial_fft(a, self.omega_inv, self.log_size);
+        a.iter_mut().for_each(|v| *v *= self.size_inv);
+    }
+
+    /// Evaluates `poly` in all the elements of the domain
+    /// # Examples
+    /// ```
+    ///    use a0kzg::{EvaluationDomain, Poly, Scalar};
+    ///    let domain = EvaluationDomain::new(4).unwrap();
+    ///    let p = Poly::from(&[1, 2, 3]);
+    ///    let evals = domain.fft(&p);
+    ///    assert_eq!(evals[1], p.eval(&domain.element(1)));
+    ///    assert_eq!(domain.ifft(&evals), p);
+    /// ```
+    pub fn fft(&self, poly: &Poly) -> Vec<Scalar> {
+        assert!(poly.0.len() <= self.size);
+        let mut a = poly.0.clone();
+        a.resize(self.size, Scalar::zero());
+        self.fft_in_place(&mut a);
+        a
+    }
+
+    /// Interpolates the polinomial that has `evals` as evaluations over the domain
+    pub fn ifft(&self, evals: &[Scalar]) -> Poly {
+        let mut a = evals.to_vec();
+        self.ifft_in_place(&mut a);
+        Poly::new(a)
+    }
+}
+
+/// Returns [1, x, x^2 ... x^(n-1)]
+pub(crate) fn powers(x: Scalar, n: usize) -> Vec<Scalar> {
+    std::iter::successors(Some(Scalar::one()), |p| Some(p * x))
+        .take(n)
+        .collect()
+}
+
+/// Inverts all the elements of `v` at the cost of one inversion, using Montgomery's
+/// trick, zero elements are left as zero
+pub(crate) fn batch_invert(v: &mut [Scalar]) {
+    let mut acc = Scalar::one();
+    let prods: Vec<Scalar> = v
+        .iter()
+        .map(|x| {
+            let prev = acc;
+            if *x != Scalar::zero() {
+                acc *= x;
+            }
+            prev
+        })
+        .collect();
+    let mut inv = acc.invert().unwrap();
+    for (x, prod) in v.iter_mut().zip(prods.iter()).rev() {
+        if *x != Scalar::zero() {
+            let x_inv = inv * prod;
+            inv *= *x;
+            *x = x_inv;
+        }
+    }
+}
+
+/// Reverses the bits of `i`, taking it as a number of `log_n` bits
+pub(crate) fn bit_reverse(i: usize, log_n: u32) -> usize {
+    if log_n == 0 {
+        0
+    } else {
+        i.reverse_bits() >> (usize::BITS - log_n)
+    }
+}
+
+/// Reorders `a` so each element moves to its bit-reversed index, `a` must have a
+/// power of two length
+pub fn bit_reverse_permutation<T>(a: &mut [T]) {
+    let n = a.len();
+    assert!(n.is_power_of_two());
+    let log_n = n.trailing_zeros();
+    for i in 0..n {
+        let j = bit_reverse(i, log_n);
+        if i < j {
+            a.swap(i, j);
+        }
+    }
+}
+
+/// Iterative Cooley-Tukey radix-2 NTT, `omega` must be a primitive 2^log_n root of unity.
+/// It works for any group where scalars act, so it is also used over G1
+fn serial_fft<T>(a: &mut [T], omega: Scalar, log_n: u32)
+where
+    T: Copy + Add<Output = T> + Sub<Output = T> + Mul<Scalar, Output = T>,
+{
+    let n = a.len();
+    bit_reverse_permutation(a);
+
+    let mut m = 1;
+    for _ in 0..log_n {
+        // twiddles for this stage are the powers of a primitive (2m)-th root of unity
+        let w_m = omega.pow_vartime(&[(n / (2 * m)) as u64, 0, 0, 0]);
+        let twiddles = powers(w_m, m);
+        for k in (0..n).step_by(2 * m) {
+            for j in 0..m {
+                let t = a[k + j + m] * twiddles[j];
+                a[k + j + m] = a[k + j] - t;
+                a[k + j] = a[k + j] + t;
+            }
+        }
+        m *= 2;
+    }
+}
+
+#[test]
+fn test_root_of_unity() {
+    let mut w = ROOT_OF_UNITY;
+    for _ in 0..TWO_ADICITY - 1 {
+        w = w.square();
+    }
+    assert_eq!(w, -Scalar::one());
+    assert_eq!(w.square(), Scalar::one());
+}
+
+#[test]
+fn test_fft_matches_eval() {
+    let domain = EvaluationDomain::new(16).unwrap();
+    let p = Poly::from(&[5, 1, 7, 0, 3, 9, 2, 8, 4, 4, 1]);
+    let evals = domain.fft(&p);
+    for (i, y) in evals.iter().enumerate() {
+        assert_eq!(*y, p.eval(&domain.element(i)));
+    }
+    assert_eq!(domain.ifft(&evals), p);
+}
+
+#[test]
+fn test_fft_g1() {
+    let domain = EvaluationDomain::new(8).unwrap();
+    let p = Poly::from(&[3, 1, 4, 1, 5, 9, 2, 6]);
+    let g = G1Projective::generator();
+    let mut a: Vec<G1Projective> = p.0.iter().map(|c| g * c).collect();
+    domain.fft_g1(&mut a);
+    let evals = domain.fft(&p);
+    for (p, y) in a.iter().zip(evals.iter()) {
+        assert_eq!(*p, g * y);
+    }
+    domain.ifft_g1(&mut a);
+    for (p, c) in a.iter().zip(p.0.iter()) {
+        assert_eq!(*p, g * c);
+    }
+}
+
+#[test]
+fn test_domain_sizes() {
+    assert_eq!(EvaluationDomain::new(0).unwrap().size(), 1);
+    assert_eq!(EvaluationDomain::new(1).unwrap().size(), 1);
+    assert_eq!(EvaluationDomain::new(4096).unwrap().size(), 4096);
+    assert_eq!(EvaluationDomain::new(4097).unwrap().log_size(), 13);
+
+    let domain = EvaluationDomain::new(8).unwrap();
+    assert_eq!(domain.omega() * domain.omega_inv(), Scalar::one());
+    assert_eq!(domain.element(8), Scalar::one());
+    assert_ne!(domain.element(4), Scalar::one());
+}
+
+#[test]
+fn test_batch_invert() {
+    let mut v = vec![
+        Scalar::from(3),
+        Scalar::zero(),
+        Scalar::from(11),
+        -Scalar::from(2),
+    ];
+    let expected: Vec<Scalar> = v
+        .iter()
+        .map(|x| x.invert().unwrap_or(Scalar::zero()))
+        .collect();
+    batch_invert(&mut v);
+    assert_eq!(v, expected);
+}
+
+#[test]
+fn test_bit_reverse_permutation() {
+    let mut a: Vec<usize> = (0..8).collect();
+    bit_reverse_permutation(&mut a);
+    assert_eq!(a, vec![0, 4, 2, 6, 1, 5, 3, 7]);
+}
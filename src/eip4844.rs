@@ -0,0 +1,331 @@
+//! This module implements the EIP-4844 blob commitments on top of Kzg, following the
+//! semantics of the Ethereum consensus specs
+//! see https://github.com/ethereum/consensus-specs/blob/dev/specs/deneb/polynomial-commitments.md
+
+use super::{
+    domain::{bit_reverse_permutation, EvaluationDomain},
+    eval_poly::EvalPoly,
+    kzg::{Kzg, KzgError},
+    msm::msm_g1,
+};
+use bls12_381::{G1Affine, G1Projective, Scalar};
+use sha2::{Digest, Sha256};
+
+pub const BYTES_PER_FIELD_ELEMENT: usize = 32;
+pub const FIELD_ELEMENTS_PER_BLOB: usize = 4096;
+pub const BYTES_PER_BLOB: usize = BYTES_PER_FIELD_ELEMENT * FIELD_ELEMENTS_PER_BLOB;
+pub const BYTES_PER_COMMITMENT: usize = 48;
+pub const BYTES_PER_PROOF: usize = 48;
+
+const FIAT_SHAMIR_PROTOCOL_DOMAIN: &[u8; 16] = b"FSBLOBVERIFY_V1_";
+const RANDOM_CHALLENGE_KZG_BATCH_DOMAIN: &[u8; 16] = b"RCKZGBATCH___V1_";
+
+/// 4096 field elements, big-endian encoded, that are the evaluations of a polinomial
+/// over the roots of unity in bit-reversed order
+pub type Blob = [u8; BYTES_PER_BLOB];
+/// A big-endian encoded field element
+pub type Bytes32 = [u8; 32];
+/// A compressed G1 point, used for commitments and proofs
+pub type Bytes48 = [u8; 48];
+
+impl Kzg {
+    /// Computes the commitment of the polinomial encoded in `blob`
+    pub fn blob_to_kzg_commitment(&self, blob: &Blob) -> Result<Bytes48, KzgError> {
+        let evals = blob_to_evals(blob)?;
+        Ok(G1Affine::from(self.commit_evals(&evals)?).to_compressed())
+    }
+
+    /// Computes the proof that the polinomial encoded in `blob` evaluates to y at
+    /// `z`, returns the proof and y
+    pub fn compute_kzg_proof(
+        &self,
+        blob: &Blob,
+        z: &Bytes32,
+    ) -> Result<(Bytes48, Bytes32), KzgError> {
+        let evals = blob_to_evals(blob)?;
+        let z = bytes_to_scalar(z)?;
+        let (y, proof) = self.open_evals(&evals, &z)?;
+        Ok((G1Affine::from(proof).to_compressed(), scalar_to_bytes(&y)))
+    }
+
+    /// Computes the proof for `blob` at the Fiat-Shamir challenge derived from the
+    /// blob and its `commitment`
+    pub fn compute_blob_kzg_proof(
+        &self,
+        blob: &Blob,
+        commitment: &Bytes48,
+    ) -> Result<Bytes48, KzgError> {
+        bytes_to_g1(commitment)?;
+        let evals = blob_to_evals(blob)?;
+        let z = compute_challenge(blob, commitment);
+        let (_, proof) = self.open_evals(&evals, &z)?;
+        Ok(G1Affine::from(proof).to_compressed())
+    }
+
+    /// Verifies that the polinomial committed in `commitment` evaluates to `y` at `z`
+    pub fn verify_kzg_proof(
+        &self,
+        commitment: &Bytes48,
+        z: &Bytes32,
+        y: &Bytes32,
+        proof: &Bytes48,
+    ) -> Result<bool, KzgError> {
+        let commitment = bytes_to_g1(commitment)?;
+        let (z, y) = (bytes_to_scalar(z)?, bytes_to_scalar(y)?);
+        let proof = bytes_to_g1(proof)?;
+        Ok(self.verify_eval(&commitment.into(), &z, &y, &proof.into()))
+    }
+
+    /// Verifies the `proof` computed with `compute_blob_kzg_proof`
+    pub fn verify_blob_kzg_proof(
+        &self,
+        blob: &Blob,
+        commitment: &Bytes48,
+        proof: &Bytes48,
+    ) -> Result<bool, KzgError> {
+        let (c, z, y) = blob_opening(blob, commitment)?;
+        let proof = bytes_to_g1(proof)?;
+        Ok(self.verify_eval(&c.into(), &z, &y, &proof.into()))
+    }
+
+    /// Verifies many proofs computed with `compute_blob_kzg_proof` at once, using a
+    /// random linear combination of them derived from all the inputs
+    pub fn verify_blob_kzg_proof_batch(
+        &self,
+        blobs: &[Blob],
+        commitments: &[Bytes48],
+        proofs: &[Bytes48],
+    ) -> Result<bool, KzgError> {
+        if blobs.len() != commitments.len() || blobs.len() != proofs.len() {
+            return Err(KzgError::LengthMismatch);
+        }
+        let mut openings = Vec::with_capacity(blobs.len());
+        for ((blob, commitment), proof) in blobs.iter().zip(commitments).zip(proofs) {
+            let (c, z, y) = blob_opening(blob, commitment)?;
+            openings.push((c, z, y, bytes_to_g1(proof)?));
+        }
+        Ok(self.verify_kzg_proof_batch(commitments, proofs, &openings))
+    }
+
+    /// Verifies the openings with `verify_batch_with`, being r the batch challenge of
+    /// the spec, derived from all the encoded openings
+    fn verify_kzg_proof_batch(
+        &self,
+        commitments: &[Bytes48],
+        proofs: &[Bytes48],
+        openings: &[(G1Affine, Scalar, Scalar, G1Affine)],
+    ) -> bool {
+        let mut hasher = Sha256::new();
+        hasher.update(RANDOM_CHALLENGE_KZG_BATCH_DOMAIN);
+        hasher.update((FIELD_ELEMENTS_PER_BLOB as u64).to_be_bytes());
+        hasher.update((openings.len() as u64).to_be_bytes());
+        for ((commitment, proof), (_, z, y, _)) in commitments.iter().zip(proofs).zip(openings) {
+            hasher.update(commitment);
+            hasher.update(scalar_to_bytes(z));
+            hasher.update(scalar_to_bytes(y));
+            hasher.update(proof);
+        }
+        let r = hash_to_scalar(hasher);
+
+        let openings: Vec<_> = openings
+            .iter()
+            .map(|(c, z, y, proof)| (c.into(), *z, *y, proof.into()))
+            .collect();
+        self.verify_batch_with(&openings, &r)
+    }
+
+    /// Commits to the polinomial with the bit-reversed evaluations `evals`, the
+    /// evaluations are put in the natural order of the Lagrange basis
+    fn commit_evals(&self, evals: &[Scalar]) -> Result<G1Projective, KzgError> {
+        let evals = evals_to_poly(evals);
+        if self.lagrange_g1.len() == FIELD_ELEMENTS_PER_BLOB {
+            Ok(msm_g1(&self.lagrange_g1, evals.evals()))
+        } else {
+            self.commit(&evals.to_poly())
+        }
+    }
+
+    /// Opens the polinomial with the bit-reversed evaluations `evals` at `z`
+    fn open_evals(&self, evals: &[Scalar], z: &Scalar) -> Result<(Scalar, G1Projective), KzgError> {
+        let (q, y) = evals_to_poly(evals).to_poly().div_by_linear(z);
+        Ok((y, self.commit(&q)?))
+    }
+}
+
+/// Decodes the commitment and computes the challenge z and the evaluation y for
+/// the blob proof of `blob`
+fn blob_opening(blob: &Blob, commitment: &Bytes48) -> Result<(G1Affine, Scalar, Scalar), KzgError> {
+    let c = bytes_to_g1(commitment)?;
+    let evals = evals_to_poly(&blob_to_evals(blob)?);
+    let z = compute_challenge(blob, commitment);
+    Ok((c, z, evals.eval(&z)))
+}
+
+/// Fiat-Shamir challenge, hash(domain | degree | blob | commitment) mod r
+fn compute_challenge(blob: &Blob, commitment: &Bytes48) -> Scalar {
+    let mut hasher = Sha256::new();
+    hasher.update(FIAT_SHAMIR_PROTOCOL_DOMAIN);
+    hasher.update((FIELD_ELEMENTS_PER_BLOB as u128).to_be_bytes());
+    hasher.update(&blob[..]);
+    hasher.update(commitment);
+    hash_to_scalar(hasher)
+}
+
+/// Takes the big-endian hash as a number modulo r
+fn hash_to_scalar(hasher: Sha256) -> Scalar {
+    let mut wide = [0u8; 64];
+    wide[..32].copy_from_slice(&hasher.finalize());
+    wide[..32].reverse();
+    Scalar::from_bytes_wide(&wide)
+}
+
+/// Decodes the field elements of the blob, keeping the bit-reversed order
+fn blob_to_evals(blob: &Blob) -> Result<Vec<Scalar>, KzgError> {
+    blob.chunks(BYTES_PER_FIELD_ELEMENT)
+        .map(|chunk| {
+            let mut bytes = [0u8; 32];
+            bytes.copy_from_slice(chunk);
+            bytes_to_scalar(&bytes)
+        })
+        .collect()
+}
+
+/// Puts the bit-reversed `evals` in natural order over the blob domain
+fn evals_to_poly(evals: &[Scalar]) -> EvalPoly {
+    let mut evals = evals.to_vec();
+    bit_reverse_permutation(&mut evals);
+    let domain = EvaluationDomain::new(FIELD_ELEMENTS_PER_BLOB).unwrap();
+    EvalPoly::new(domain, evals)
+}
+
+/// Decodes a big-endian field element, failing if it is not canonical (>= r)
+fn bytes_to_scalar(bytes: &Bytes32) -> Result<Scalar, KzgError> {
+    let mut le = *bytes;
+    le.reverse();
+    let s: Option<Scalar> = Scalar::from_bytes(&le).into();
+    s.ok_or(KzgError::InvalidScalar)
+}
+
+fn scalar_to_bytes(s: &Scalar) -> Bytes32 {
+    let mut bytes = s.to_bytes();
+    bytes.reverse();
+    bytes
+}
+
+/// Decodes a compressed G1 point, failing if it is not on the curve or not in the
+/// subgroup. The point at infinity is valid
+fn bytes_to_g1(bytes: &Bytes48) -> Result<G1Affine, KzgError> {
+    let p: Option<G1Affine> = G1Affine::from_compressed(bytes).into();
+    p.ok_or(KzgError::InvalidPoint)
+}
+
+#[cfg(test)]
+fn random_blob() -> Box<Blob> {
+    use rand::Rng;
+    let mut rng = rand::thread_rng();
+    let mut blob = Box::new([0u8; BYTES_PER_BLOB]);
+    for chunk in blob.chunks_mut(BYTES_PER_FIELD_ELEMENT) {
+        let s = Scalar::from_raw([rng.gen(), rng.gen(), rng.gen(), rng.gen()]);
+        chunk.copy_from_slice(&scalar_to_bytes(&s));
+    }
+    blob
+}
+
+#[test]
+fn test_blob_proofs() {
+    let kzg = super::setup::ethereum_kzg();
+    let blobs = vec![*random_blob(), *random_blob(), *random_blob()];
+    let commitments: Vec<Bytes48> = blobs
+        .iter()
+        .map(|blob| kzg.blob_to_kzg_commitment(blob).unwrap())
+        .collect();
+    let proofs: Vec<Bytes48> = blobs
+        .iter()
+        .zip(commitments.iter())
+        .map(|(blob, c)| kzg.compute_blob_kzg_proof(blob, c).unwrap())
+        .collect();
+
+    assert_eq!(
+        kzg.verify_blob_kzg_proof(&blobs[0], &commitments[0], &proofs[0]),
+        Ok(true)
+    );
+    assert_eq!(
+        kzg.verify_blob_kzg_proof(&blobs[0], &commitments[0], &proofs[1]),
+        Ok(false)
+    );
+    assert_eq!(
+        kzg.verify_blob_kzg_proof_batch(&blobs, &commitments, &proofs),
+        Ok(true)
+    );
+    assert_eq!(
+        kzg.verify_blob_kzg_proof_batch(&blobs, &commitments, &[proofs[0], proofs[2], proofs[1]]),
+        Ok(false)
+    );
+    assert_eq!(kzg.verify_blob_kzg_proof_batch(&[], &[], &[]), Ok(true));
+    assert_eq!(
+        kzg.verify_blob_kzg_proof_batch(&blobs, &commitments, &proofs[1..]),
+        Err(KzgError::LengthMismatch)
+    );
+
+    // the commitment through the Lagrange basis matches the monomial one
+    let evals = blob_to_evals(&blobs[0]).unwrap();
+    let c = G1Affine::from(kzg.commit(&evals_to_poly(&evals).to_poly()).unwrap());
+    assert_eq!(c.to_compressed(), commitments[0]);
+}
+
+#[test]
+fn test_kzg_proof() {
+    let kzg = super::setup::ethereum_kzg();
+    let blob = random_blob();
+    let commitment = kzg.blob_to_kzg_commitment(&blob).unwrap();
+
+    // a point outside and a point inside the domain
+    let z_out = scalar_to_bytes(&Scalar::from(42));
+    let z_in = scalar_to_bytes(
+        &EvaluationDomain::new(FIELD_ELEMENTS_PER_BLOB)
+            .unwrap()
+            .element(1),
+    );
+    for z in [z_out, z_in].iter() {
+        let (proof, y) = kzg.compute_kzg_proof(&blob, z).unwrap();
+        assert_eq!(kzg.verify_kzg_proof(&commitment, z, &y, &proof), Ok(true));
+        let y_bad = scalar_to_bytes(&(bytes_to_scalar(&y).unwrap() + Scalar::one()));
+        assert_eq!(
+            kzg.verify_kzg_proof(&commitment, z, &y_bad, &proof),
+            Ok(false)
+        );
+    }
+    // y is the first element of the blob at z=1 since bit_reverse(0) = 0
+    let (_, y) = kzg
+        .compute_kzg_proof(&blob, &scalar_to_bytes(&Scalar::one()))
+        .unwrap();
+    assert_eq!(&y[..], &blob[..32]);
+}
+
+#[test]
+fn test_invalid_inputs() {
+    let kzg = super::setup::ethereum_kzg();
+    let mut blob = random_blob();
+    // r itself is not a canonical field element
+    let modulus = scalar_to_bytes(&-Scalar::one());
+    blob[..32].copy_from_slice(&modulus);
+    blob[31] += 1;
+    assert_eq!(
+        kzg.blob_to_kzg_commitment(&blob),
+        Err(KzgError::InvalidScalar)
+    );
+
+    let mut not_on_curve = [0u8; 48];
+    not_on_curve[0] = 0x80;
+    not_on_curve[47] = 1;
+    let zero = [0u8; 32];
+    let infinity = G1Affine::identity().to_compressed();
+    assert_eq!(
+        kzg.verify_kzg_proof(&not_on_curve, &zero, &zero, &infinity),
+        Err(KzgError::InvalidPoint)
+    );
+    assert_eq!(
+        kzg.verify_kzg_proof(&infinity, &zero, &zero, &infinity),
+        Ok(true)
+    );
+}
@@ -0,0 +1,458 @@
+//! This module implements the EIP-6800 layout of the Ethereum state in a Verkle tree,
+//! the keys are derived from the account address with a Pedersen hash and the account
+//! header (basic data and code hash) and code chunks live in the same leaves
+//! see https://eips.ethereum.org/EIPS/eip-6800
+
+use super::{
+    bandersnatch::BandersnatchScalar,
+    ipa::Ipa,
+    kzg::KzgError,
+    verkle::{VerkleKey, VerkleTree, VerkleValue, VERKLE_NODE_WIDTH},
+    verkle_store::VerkleStore,
+};
+use sha3::{Digest, Keccak256};
+
+pub const BASIC_DATA_LEAF_KEY: u8 = 0;
+pub const CODE_HASH_LEAF_KEY: u8 = 1;
+pub const HEADER_STORAGE_OFFSET: u64 = 64;
+pub const CODE_OFFSET: u64 = 128;
+/// Bytes of code in each chunk, the first byte of the chunk is the pushdata count
+pub const CODE_CHUNK_SIZE: usize = 31;
+
+const PUSH1: u8 = 0x60;
+const PUSH32: u8 = 0x7f;
+
+/// Errors that can happen when deriving the keys or encoding the values of an account
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Eip6800Error {
+    /// The input of the Pedersen hash has more bytes than supported
+    InputTooLong { len: usize, max: usize },
+    /// The code size of an account does not fit in its 3 bytes of the basic data
+    CodeSizeTooLarge(u32),
+    /// The Pedersen commitment of the input failed
+    Commitment(KzgError),
+}
+
+impl std::fmt::Display for Eip6800Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Eip6800Error::InputTooLong { len, max } => {
+                write!(f, "input of {} bytes exceeds the maximum {}", len, max)
+            }
+            Eip6800Error::CodeSizeTooLarge(size) => {
+                write!(f, "code size {} does not fit in 3 bytes", size)
+            }
+            Eip6800Error::Commitment(err) => write!(f, "commitment error: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for Eip6800Error {}
+
+impl From<KzgError> for Eip6800Error {
+    fn from(err: KzgError) -> Self {
+        Eip6800Error::Commitment(err)
+    }
+}
+
+/// An account address
+pub type Address = [u8; 20];
+/// An address left-padded with zeros to 32 bytes
+pub type Address32 = [u8; 32];
+
+/// The fields of the account header that are packed in the basic data leaf, all of
+/// them big-endian encoded
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BasicData {
+    /// Byte 0
+    pub version: u8,
+    /// Bytes 5..8, so it must be less than 2^24
+    pub code_size: u32,
+    /// Bytes 8..16
+    pub nonce: u64,
+    /// Bytes 16..32
+    pub balance: u128,
+}
+
+impl BasicData {
+    /// Packs the fields in a leaf value, failing if the code size does not fit in 3
+    /// bytes
+    /// # Example
+    /// ```
+    /// use a0kzg::{BasicData, Eip6800Error};
+    /// let data = BasicData { version: 0, code_size: 3, nonce: 1, balance: 2 };
+    /// let value = data.to_value().unwrap();
+    /// assert_eq!(value[7], 3);
+    /// assert_eq!(value[15], 1);
+    /// assert_eq!(value[31], 2);
+    /// assert_eq!(BasicData::from_value(&value), data);
+    ///
+    /// let large = BasicData { code_size: 1 << 24, ..data };
+    /// assert_eq!(large.to_value(), Err(Eip6800Error::CodeSizeTooLarge(1 << 24)));
+    /// ```
+    pub fn to_value(&self) -> Result<VerkleValue, Eip6800Error> {
+        if self.code_size >= 1 << 24 {
+            return Err(Eip6800Error::CodeSizeTooLarge(self.code_size));
+        }
+        let mut value = [0u8; 32];
+        value[0] = self.version;
+        value[5..8].copy_from_slice(&self.code_size.to_be_bytes()[1..]);
+        value[8..16].copy_from_slice(&self.nonce.to_be_bytes());
+        value[16..32].copy_from_slice(&self.balance.to_be_bytes());
+        Ok(value)
+    }
+
+    /// Unpacks the fields of a leaf value
+    pub fn from_value(value: &VerkleValue) -> Self {
+        let mut code_size = [0u8; 4];
+        code_size[1..].copy_from_slice(&value[5..8]);
+        let mut nonce = [0u8; 8];
+        nonce.copy_from_slice(&value[8..16]);
+        let mut balance = [0u8; 16];
+        balance.copy_from_slice(&value[16..32]);
+        Self {
+            version: value[0],
+            code_size: u32::from_be_bytes(code_size),
+            nonce: u64::from_be_bytes(nonce),
+            balance: u128::from_be_bytes(balance),
+        }
+    }
+}
+
+/// Left-pads the address with zeros
+pub fn address32(address: &Address) -> Address32 {
+    let mut address32 = [0u8; 32];
+    address32[12..].copy_from_slice(address);
+    address32
+}
+
+/// Splits the code in 32 byte chunks, each one with 31 bytes of code prefixed with the
+/// number of leading bytes that are PUSHn data, so the code can be executed from any
+/// chunk. The last chunk is padded with zeros
+/// # Example
+/// ```
+/// use a0kzg::chunkify_code;
+/// // PUSH4 0x01020304, STOP
+/// let chunks = chunkify_code(&[0x63, 1, 2, 3, 4, 0]);
+/// assert_eq!(chunks.len(), 1);
+/// assert_eq!(chunks[0][..8], [0, 0x63, 1, 2, 3, 4, 0, 0]);
+/// ```
+pub fn chunkify_code(code: &[u8]) -> Vec<VerkleValue> {
+    // the remaining pushdata bytes at each position of the code
+    let mut pushdata = vec![0usize; code.len()];
+    let mut pos = 0;
+    while pos < code.len() {
+        let len = if (PUSH1..=PUSH32).contains(&code[pos]) {
+            (code[pos] - PUSH1 + 1) as usize
+        } else {
+            0
+        };
+        pos += 1;
+        for (x, remaining) in pushdata.iter_mut().skip(pos).take(len).enumerate() {
+            *remaining = len - x;
+        }
+        pos += len;
+    }
+
+    code.chunks(CODE_CHUNK_SIZE)
+        .enumerate()
+        .map(|(i, chunk)| {
+            let mut value = [0u8; 32];
+            value[0] = pushdata[i * CODE_CHUNK_SIZE].min(CODE_CHUNK_SIZE) as u8;
+            value[1..=chunk.len()].copy_from_slice(chunk);
+            value
+        })
+        .collect()
+}
+
+impl Ipa {
+    /// Commits to `input` as 16 byte little-endian integers, prefixed with
+    /// 2 + 256·len(input), and returns the commitment mapped to a field, little-endian
+    /// encoded. The input can have up to 255·16 bytes
+    pub fn pedersen_hash(&self, input: &[u8]) -> Result<[u8; 32], Eip6800Error> {
+        let max = (VERKLE_NODE_WIDTH - 1) * 16;
+        if input.len() > max {
+            return Err(Eip6800Error::InputTooLong {
+                len: input.len(),
+                max,
+            });
+        }
+        let mut ints = vec![BandersnatchScalar::from(2 + 256 * input.len() as u64)];
+        ints.extend(
+            input
+                .chunks(16)
+                .map(BandersnatchScalar::from_le_bytes_mod_order),
+        );
+        let mut hash = [0u8; 32];
+        hash.copy_from_slice(
+            &self
+                .commit(&ints)?
+                .map_to_field()
+                .to_bls_scalar()
+                .to_bytes(),
+        );
+        Ok(hash)
+    }
+
+    /// Derives the key of the `sub_index` value of the leaf `tree_index` of the account
+    /// at `address`, being the stem the first 31 bytes of
+    /// pedersen_hash(address|tree_index), with tree_index as 32 little-endian bytes
+    /// # Example
+    /// ```
+    /// use a0kzg::{address32, Ipa};
+    /// let ipa = Ipa::new(256);
+    /// let address = address32(&[0x11; 20]);
+    /// let key = ipa.get_tree_key(&address, &[0; 32], 1).unwrap();
+    /// assert_eq!(key[31], 1);
+    /// assert_eq!(key[..31], ipa.get_tree_key(&address, &[0; 32], 2).unwrap()[..31]);
+    /// ```
+    pub fn get_tree_key(
+        &self,
+        address: &Address32,
+        tree_index: &[u8; 32],
+        sub_index: u8,
+    ) -> Result<VerkleKey, Eip6800Error> {
+        let mut input = [0u8; 64];
+        input[..32].copy_from_slice(address);
+        input[32..].copy_from_slice(tree_index);
+        let mut key = self.pedersen_hash(&input)?;
+        key[31] = sub_index;
+        Ok(key)
+    }
+
+    /// Returns the key of the basic data of the account
+    pub fn basic_data_key(&self, address: &Address) -> Result<VerkleKey, Eip6800Error> {
+        self.get_tree_key(&address32(address), &[0; 32], BASIC_DATA_LEAF_KEY)
+    }
+
+    /// Returns the key of the code hash of the account
+    pub fn code_hash_key(&self, address: &Address) -> Result<VerkleKey, Eip6800Error> {
+        self.get_tree_key(&address32(address), &[0; 32], CODE_HASH_LEAF_KEY)
+    }
+
+    /// Returns the key of the `chunk_id` chunk of the code of the account, the first
+    /// 128 chunks share the leaf of the account header
+    pub fn code_chunk_key(
+        &self,
+        address: &Address,
+        chunk_id: u64,
+    ) -> Result<VerkleKey, Eip6800Error> {
+        let pos = CODE_OFFSET + chunk_id;
+        let mut tree_index = [0u8; 32];
+        tree_index[..8].copy_from_slice(&(pos / VERKLE_NODE_WIDTH as u64).to_le_bytes());
+        self.get_tree_key(
+            &address32(address),
+            &tree_index,
+            (pos % VERKLE_NODE_WIDTH as u64) as u8,
+        )
+    }
+}
+
+impl<S: VerkleStore> VerkleTree<Ipa, S> {
+    /// Stores the basic data of the account at `address`, failing if it can not be
+    /// packed, see `BasicData::to_value`
+    /// # Example
+    /// ```
+    /// use a0kzg::{BasicData, VerkleTree};
+    /// let mut tree = VerkleTree::new_ipa();
+    /// let data = BasicData { balance: 1000, ..Default::default() };
+    /// tree.insert_basic_data(&[1; 20], &data).unwrap();
+    /// tree.insert_code(&[1; 20], &[0x60, 0x01, 0x00]);
+    /// assert_eq!(tree.basic_data(&[1; 20]), Some(data));
+    /// ```
+    pub fn insert_basic_data(
+        &mut self,
+        address: &Address,
+        data: &BasicData,
+    ) -> Result<(), Eip6800Error> {
+        let key = self.committer.basic_data_key(address)?;
+        self.insert(key, data.to_value()?);
+        Ok(())
+    }
+
+    /// Returns the basic data of the account at `address`
+    pub fn basic_data(&self, address: &Address) -> Option<BasicData> {
+        let key = self.committer.basic_data_key(address).unwrap();
+        self.get(&key).as_ref().map(BasicData::from_value)
+    }
+
+    /// Stores the keccak256 hash and the chunks of `code` for the account at
+    /// `address`. The code size of the basic data is not updated
+    pub fn insert_code(&mut self, address: &Address, code: &[u8]) {
+        let key = self.committer.code_hash_key(address).unwrap();
+        self.insert(key, Keccak256::digest(code).into());
+        for (i, chunk) in chunkify_code(code).into_iter().enumerate() {
+            let key = self.committer.code_chunk_key(address, i as u64).unwrap();
+            self.insert(key, chunk);
+        }
+    }
+}
+
+#[test]
+fn test_chunkify_code() {
+    assert!(chunkify_code(&[]).is_empty());
+
+    // PUSH32 at the end of the first chunk, so the next chunk is all pushdata and the
+    // third one starts with one byte of pushdata
+    let mut code = vec![0u8; 30];
+    code.push(PUSH32);
+    code.extend(vec![0xaa; 32]);
+    code.push(0x01);
+    let chunks = chunkify_code(&code);
+    assert_eq!(chunks.len(), 3);
+    assert_eq!(chunks[0][0], 0);
+    assert_eq!(chunks[0][31], PUSH32);
+    assert_eq!(chunks[1][0], 31);
+    assert_eq!(chunks[1][1..], [0xaa; 31]);
+    assert_eq!(chunks[2][..3], [1, 0xaa, 0x01]);
+    assert_eq!(chunks[2][3..], [0; 29]);
+
+    // a PUSH in the pushdata is data
+    let chunks = chunkify_code(&[PUSH1 + 1, PUSH1, PUSH1, PUSH1, 0x00]);
+    assert_eq!(chunks[0][..6], [0, PUSH1 + 1, PUSH1, PUSH1, PUSH1, 0x00]);
+    let mut code = vec![0u8; 29];
+    code.extend([PUSH1 + 2, PUSH1, PUSH1, PUSH1]);
+    assert_eq!(chunkify_code(&code)[1][..4], [2, PUSH1, PUSH1, 0]);
+}
+
+#[test]
+fn test_get_tree_key() {
+    use super::bandersnatch::{msm_bandersnatch, pedersen_basis};
+
+    let ipa = Ipa::new(VERKLE_NODE_WIDTH);
+    let address = [0x5a; 20];
+
+    // pedersen_hash(address32|tree_index) = (2 + 256·64)·G_0 + Σ chunk_i·G_(i+1),
+    // being chunk_i the 16 byte little-endian chunks of the input
+    let address32 = address32(&address);
+    let mut tree_index = [0u8; 32];
+    tree_index[0] = 1;
+    let ints: Vec<BandersnatchScalar> = [2 + 256 * 64]
+        .iter()
+        .map(|n| BandersnatchScalar::from(*n))
+        .chain(
+            address32
+                .chunks(16)
+                .chain(tree_index.chunks(16))
+                .map(BandersnatchScalar::from_le_bytes_mod_order),
+        )
+        .collect();
+    let commitment = msm_bandersnatch(&pedersen_basis(VERKLE_NODE_WIDTH), &ints);
+    let key = ipa.get_tree_key(&address32, &tree_index, 72).unwrap();
+    assert_eq!(
+        key[..31],
+        commitment.map_to_field().to_bls_scalar().to_bytes()[..31]
+    );
+    assert_eq!(key[31], 72);
+    // the code chunk 200 is at the position 128 + 200 = 256 + 72
+    assert_eq!(ipa.code_chunk_key(&address, 200).unwrap(), key);
+
+    // the header and the first 128 code chunks share the stem
+    let basic = ipa.basic_data_key(&address).unwrap();
+    let code_hash = ipa.code_hash_key(&address).unwrap();
+    let chunk0 = ipa.code_chunk_key(&address, 0).unwrap();
+    let chunk127 = ipa.code_chunk_key(&address, 127).unwrap();
+    assert_eq!(
+        [basic[31], code_hash[31], chunk0[31], chunk127[31]],
+        [0, 1, 128, 255]
+    );
+    assert!([code_hash, chunk0, chunk127]
+        .iter()
+        .all(|k| k[..31] == basic[..31]));
+    assert_ne!(key[..31], basic[..31]);
+    assert_ne!(ipa.basic_data_key(&[0x5b; 20]).unwrap()[..31], basic[..31]);
+
+    assert!(ipa.pedersen_hash(&[0; 255 * 16]).is_ok());
+    assert_eq!(
+        ipa.pedersen_hash(&[0; 255 * 16 + 1]),
+        Err(Eip6800Error::InputTooLong {
+            len: 255 * 16 + 1,
+            max: 255 * 16
+        })
+    );
+    assert_eq!(
+        Ipa::new(4).pedersen_hash(&[0; 64]),
+        Err(Eip6800Error::Commitment(KzgError::DegreeTooLarge {
+            degree: 4,
+            max: 3
+        }))
+    );
+}
+
+#[test]
+fn test_eip6800_roots() {
+    let hash = |tree: &VerkleTree<Ipa>| {
+        hex::encode(
+            tree.root_commitment()
+                .map_to_field()
+                .to_bls_scalar()
+                .to_bytes(),
+        )
+    };
+
+    // the root commitments mapped to a field of the insert_key0value0 and
+    // insert_key1_value1 tests of rust-verkle, https://github.com/crate-crypto/rust-verkle
+    let mut tree = VerkleTree::new_ipa();
+    tree.insert([0; 32], [0; 32]);
+    assert_eq!(
+        hash(&tree),
+        "ff00a9f3f2d4f58fc23bceebf6b2310419ceac2c30445e2f374e571487715015"
+    );
+    let mut key = [0u8; 32];
+    for (i, b) in key.iter_mut().enumerate() {
+        *b = i as u8 + 1;
+    }
+    let mut tree = VerkleTree::new_ipa();
+    tree.insert(key, key);
+    assert_eq!(
+        hash(&tree),
+        "029b6c4c8af9001f0ac76472766c6579f41eec84a73898da06eb97ebdab80a09"
+    );
+    // computed with this implementation, not a published vector
+    assert_eq!(
+        hex::encode(tree.committer.basic_data_key(&[0; 20]).unwrap()),
+        "1a100684fd68185060405f3f160e4bb6e034194336b547bdae323f888d533200"
+    );
+
+    // two accounts, one of them with code in two chunks. There is no published root
+    // for the account layout, so the root is only a regression value of this
+    // implementation, and the layout is checked reading the values back
+    let mut tree = VerkleTree::new_ipa();
+    let code: Vec<u8> = (0..40u8)
+        .map(|i| i.wrapping_mul(13).wrapping_add(0x55))
+        .collect();
+    let data = BasicData {
+        version: 0,
+        code_size: code.len() as u32,
+        nonce: 7,
+        balance: 1_000_000,
+    };
+    tree.insert_basic_data(&[0x11; 20], &data).unwrap();
+    tree.insert_code(&[0x11; 20], &code);
+    let data = BasicData {
+        nonce: 1,
+        balance: 5,
+        ..Default::default()
+    };
+    tree.insert_basic_data(&[0x22; 20], &data).unwrap();
+    assert_eq!(
+        hash(&tree),
+        "99924f52859f22907b362a00d16509fc752febde64a5de56ac2840571c7a480a"
+    );
+    let chunks = chunkify_code(&code);
+    assert_eq!(chunks.len(), 2);
+    for (i, chunk) in chunks.iter().enumerate() {
+        let key = tree
+            .committer
+            .code_chunk_key(&[0x11; 20], i as u64)
+            .unwrap();
+        assert_eq!(tree.get(&key), Some(*chunk));
+    }
+    assert_eq!(tree.basic_data(&[0x22; 20]), Some(data));
+    assert_eq!(tree.basic_data(&[0x33; 20]), None);
+    let code_hash = tree.committer.code_hash_key(&[0x11; 20]).unwrap();
+    assert_eq!(
+        hex::encode(tree.get(&code_hash).unwrap()),
+        "e427e65ee8644a3efad8ae37139ffa6ec8997a96ba1837205336091e776b0fe0"
+    );
+}
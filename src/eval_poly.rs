@@ -0,0 +1,165 @@
+//! This module provides polinomials in evaluation form, that is, stored as their
+//! evaluations over an EvaluationDomain
+
+use super::{
+    domain::{batch_invert, EvaluationDomain},
+    poly::Poly,
+};
+use bls12_381::Scalar;
+
+/// A polinomial of degree < n represented by its evaluations p(w^0), p(w^1) ...
+/// p(w^(n-1)) over an evaluation domain of size n
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EvalPoly {
+    domain: EvaluationDomain,
+    evals: Vec<Scalar>,
+}
+
+impl EvalPoly {
+    /// Creates a new EvalPoly from its `evals`uations over `domain`, the number of
+    /// evaluations must be the same that the size of the domain
+    pub fn new(domain: EvaluationDomain, evals: Vec<Scalar>) -> Self {
+        assert_eq!(evals.len(), domain.size());
+        Self { domain, evals }
+    }
+
+    /// Converts a polinomial in coefficient form into evaluation form over `domain`
+    /// # Examples
+    /// ```
+    ///    use a0kzg::{EvalPoly, EvaluationDomain, Poly};
+    ///    let p = Poly::from(&[1, 2, 1]);
+    ///    let e = EvalPoly::from_poly(&p, EvaluationDomain::new(4).unwrap());
+    ///    assert_eq!(e.to_poly(), p);
+    /// ```
+    pub fn from_poly(poly: &Poly, domain: EvaluationDomain) -> Self {
+        let evals = domain.fft(poly);
+        Self { domain, evals }
+    }
+
+    /// Converts the polinomial into coefficient form
+    pub fn to_poly(&self) -> Poly {
+        self.domain.ifft(&self.evals)
+    }
+
+    /// Returns the domain where the polinomial is evaluated
+    pub fn domain(&self) -> &EvaluationDomain {
+        &self.domain
+    }
+
+    /// Returns the evaluations of the polinomial over the domain
+    pub fn evals(&self) -> &[Scalar] {
+        &self.evals
+    }
+
+    /// Evals the polinomial at any point `x` with the barycentric formula
+    ///   p(x) = (x^n - 1)/n · Σ p(w^i)·w^i/(x - w^i)
+    /// without converting it to coefficient form
+    /// # Examples
+    /// ```
+    ///    use a0kzg::{EvalPoly, EvaluationDomain, Poly, Scalar};
+    ///    let p = Poly::from(&[1, 2, 1]);
+    ///    let e = EvalPoly::from_poly(&p, EvaluationDomain::new(4).unwrap());
+    ///    assert_eq!(e.eval(&Scalar::from(2)), Scalar::from(9));
+    /// ```
+    pub fn eval(&self, x: &Scalar) -> Scalar {
+        let n = self.domain.size();
+        let roots = self.domain.elements();
+
+        let mut denoms: Vec<Scalar> = roots.iter().map(|w| x - w).collect();
+        if let Some(i) = denoms.iter().position(|d| *d == Scalar::zero()) {
+            return self.evals[i];
+        }
+        batch_invert(&mut denoms);
+
+        let sum = self
+            .evals
+            .iter()
+            .zip(roots.iter())
+            .zip(denoms.iter())
+            .fold(Scalar::zero(), |acc, ((y, w), d)| acc + y * w * d);
+
+        let x_pow_n = x.pow_vartime(&[n as u64, 0, 0, 0]);
+        (x_pow_n - Scalar::one()) * Scalar::from(n as u64).invert().unwrap() * sum
+    }
+}
+
+impl std::ops::AddAssign<&EvalPoly> for EvalPoly {
+    fn add_assign(&mut self, rhs: &EvalPoly) {
+        assert_eq!(self.domain, rhs.domain);
+        self.evals
+            .iter_mut()
+            .zip(rhs.evals.iter())
+            .for_each(|(a, b)| *a += b);
+    }
+}
+
+impl std::ops::SubAssign<&EvalPoly> for EvalPoly {
+    fn sub_assign(&mut self, rhs: &EvalPoly) {
+        assert_eq!(self.domain, rhs.domain);
+        self.evals
+            .iter_mut()
+            .zip(rhs.evals.iter())
+            .for_each(|(a, b)| *a -= b);
+    }
+}
+
+/// Pointwise multiplication, notice that the result only matches the product of
+/// the polinomials if its degree is smaller than the size of the domain
+impl std::ops::Mul<&EvalPoly> for &EvalPoly {
+    type Output = EvalPoly;
+    fn mul(self, rhs: &EvalPoly) -> Self::Output {
+        assert_eq!(self.domain, rhs.domain);
+        EvalPoly {
+            domain: self.domain.clone(),
+            evals: self
+                .evals
+                .iter()
+                .zip(rhs.evals.iter())
+                .map(|(a, b)| a * b)
+                .collect(),
+        }
+    }
+}
+
+impl std::ops::Mul<&Scalar> for &EvalPoly {
+    type Output = EvalPoly;
+    fn mul(self, rhs: &Scalar) -> Self::Output {
+        EvalPoly {
+            domain: self.domain.clone(),
+            evals: self.evals.iter().map(|v| v * rhs).collect(),
+        }
+    }
+}
+
+#[test]
+fn test_eval_poly_ops() {
+    let domain = EvaluationDomain::new(8).unwrap();
+    let (p1, p2) = (Poly::from(&[1, 2, 3]), Poly::from(&[4, 0, 5, 6]));
+    let (e1, e2) = (
+        EvalPoly::from_poly(&p1, domain.clone()),
+        EvalPoly::from_poly(&p2, domain),
+    );
+
+    assert_eq!((&e1 * &e2).to_poly(), &p1 * &p2);
+    assert_eq!((&e1 * &Scalar::from(3)).to_poly(), &p1 * &Scalar::from(3));
+
+    let mut sum = e1.clone();
+    sum += &e2;
+    let mut p_sum = p1.clone();
+    p_sum += &p2;
+    assert_eq!(sum.to_poly(), p_sum);
+
+    sum -= &e2;
+    assert_eq!(sum, e1);
+}
+
+#[test]
+fn test_eval_poly_barycentric() {
+    let domain = EvaluationDomain::new(16).unwrap();
+    let p = Poly::from(&[9, 8, 7, 6, 5, 4, 3, 2, 1, 0, 1, 2, 3]);
+    let e = EvalPoly::from_poly(&p, domain.clone());
+    for x in [0u64, 1, 2, 1234567].iter() {
+        assert_eq!(e.eval(&Scalar::from(*x)), p.eval(&Scalar::from(*x)));
+    }
+    assert_eq!(e.eval(&domain.element(5)), p.eval(&domain.element(5)));
+}
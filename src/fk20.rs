@@ -0,0 +1,181 @@
+//! This module implements the Feist-Khovratovich algorithm, that computes the
+//! opening proofs of a polinomial at all the points of a domain in O(n log n)
+//! see https://github.com/khovratovich/Kate/blob/master/Kate_amortized.pdf
+//!
+//! The proof at z of f = Σ f_k·X^k dividing by (X^l - z^l) is Σ_m z^(l·m)·h_m, being
+//! h_m = Σ_j f_(j+l·(m+1))·[tau^j]₁, so the proofs of all the points (or cosets) of
+//! a domain are the FFT over G1 of h, and h is a Toeplitz matrix-vector product that
+//! is computed with FFTs of twice its size
+
+use super::{
+    domain::EvaluationDomain,
+    kzg::{Kzg, KzgError, Proof},
+    poly::Poly,
+};
+use bls12_381::{G1Affine, G1Projective, Scalar};
+
+impl Kzg {
+    /// Computes the proofs that open `poly` at each element of `domain`, the i-th
+    /// proof opens poly at w^i. The polinomial should have less coefficients than
+    /// the domain size
+    /// # Example
+    /// ```
+    /// use a0kzg::{EvaluationDomain, Kzg, Poly};
+    /// let kzg = Kzg::trusted_setup(8);
+    /// let p = Poly::from(&[1, 2, 3, 4, 5, 6, 7, 8]);
+    /// let domain = EvaluationDomain::new(8).unwrap();
+    /// let proofs = kzg.compute_all_proofs(&p, &domain).unwrap();
+    /// assert_eq!(proofs[3], kzg.open_at(&p, &domain.element(3)).unwrap().1);
+    /// ```
+    pub fn compute_all_proofs(
+        &self,
+        poly: &Poly,
+        domain: &EvaluationDomain,
+    ) -> Result<Vec<Proof>, KzgError> {
+        self.compute_coset_proofs(poly, domain, 1)
+    }
+
+    /// Computes the proofs that open `poly` at each of the cosets of size `l` of
+    /// `domain`, the i-th coset is {w^(i + t·n/l) for t in 0..l}, that are the roots
+    /// of X^l - w^(i·l). There are n/l cosets and the proofs can be verified with
+    /// `verify` with the points of each coset. Fails if `l` is not a power of two no
+    /// larger than the domain
+    /// # Example
+    /// ```
+    /// use a0kzg::{EvaluationDomain, Kzg, Poly};
+    /// let kzg = Kzg::trusted_setup(8);
+    /// let p = Poly::from(&[1, 2, 3, 4, 5, 6, 7, 8]);
+    /// let c = kzg.commit(&p).unwrap();
+    /// let domain = EvaluationDomain::new(8).unwrap();
+    /// let proofs = kzg.compute_coset_proofs(&p, &domain, 4).unwrap();
+    ///
+    /// // the coset 1 is {w, w^3, w^5, w^7}
+    /// let points: Vec<_> = [1, 3, 5, 7]
+    ///     .iter()
+    ///     .map(|i| (domain.element(*i), p.eval(&domain.element(*i))))
+    ///     .collect();
+    /// assert!(kzg.verify(&c, &points, &proofs[1]));
+    /// ```
+    pub fn compute_coset_proofs(
+        &self,
+        poly: &Poly,
+        domain: &EvaluationDomain,
+        l: usize,
+    ) -> Result<Vec<Proof>, KzgError> {
+        let n = domain.size();
+        if !l.is_power_of_two() || l > n {
+            return Err(KzgError::InvalidCosetSize { size: l, domain: n });
+        }
+        if poly.degree() >= n {
+            return Err(KzgError::DegreeTooLarge {
+                degree: poly.degree(),
+                max: n - 1,
+            });
+        }
+        Self::check_degree(poly, self.pow_tau_g1.len())?;
+
+        // h = Σ_v h^v, being h^v the product for the coefficients and powers of tau
+        // whose index is v mod l
+        let cosets = n / l;
+        let mut h = vec![G1Projective::identity(); cosets];
+        for v in 0..l {
+            let f: Vec<Scalar> = poly.0.iter().skip(v).step_by(l).copied().collect();
+            let s: Vec<G1Affine> = self.pow_tau_g1.iter().skip(v).step_by(l).copied().collect();
+            for (h, h_v) in h.iter_mut().zip(toeplitz_product(&f, &s)) {
+                *h += h_v;
+            }
+        }
+
+        EvaluationDomain::new(cosets).unwrap().fft_g1(&mut h);
+        Ok(h)
+    }
+}
+
+/// Computes h_m = Σ_u f_(u+m+1)·s_u for m in 0..f.len()-1. Being D the degree of f and
+/// a_k = s_(D-1-k), h_m is the coefficient m+D of the product f·a, and this product
+/// is computed with FFTs over G1 of size 2·D
+fn toeplitz_product(f: &[Scalar], s: &[G1Affine]) -> Vec<G1Projective> {
+    if f.len() < 2 {
+        return Vec::new();
+    }
+    let d = f.len() - 1;
+    let domain = EvaluationDomain::new(2 * d).unwrap();
+
+    let mut a = vec![G1Projective::identity(); domain.size()];
+    for (k, a) in a.iter_mut().take(d).enumerate() {
+        *a = s[d - 1 - k].into();
+    }
+    domain.fft_g1(&mut a);
+
+    let mut f_evals = f.to_vec();
+    f_evals.resize(domain.size(), Scalar::zero());
+    domain.fft_in_place(&mut f_evals);
+
+    for (a, f) in a.iter_mut().zip(f_evals.iter()) {
+        *a *= f;
+    }
+    domain.ifft_g1(&mut a);
+    a[d..2 * d].to_vec()
+}
+
+#[test]
+fn test_fk20_all_proofs() {
+    let kzg = Kzg::trusted_setup(16);
+    let domain = EvaluationDomain::new(16).unwrap();
+    for len in [1usize, 2, 5, 16].iter() {
+        let p = Poly::new((0..*len as u64).map(|i| Scalar::from(i * i + 3)).collect());
+        let proofs = kzg.compute_all_proofs(&p, &domain).unwrap();
+        assert_eq!(proofs.len(), 16);
+        for (i, proof) in proofs.iter().enumerate() {
+            assert_eq!(*proof, kzg.open_at(&p, &domain.element(i)).unwrap().1);
+        }
+    }
+
+    let p = Poly::new((0..17u64).map(Scalar::from).collect());
+    assert_eq!(
+        kzg.compute_all_proofs(&p, &domain),
+        Err(KzgError::DegreeTooLarge {
+            degree: 16,
+            max: 15
+        })
+    );
+    // the domain can be larger than the setup if the polinomial fits in the setup
+    let kzg = Kzg::trusted_setup(8);
+    let p = Poly::new((0..8u64).map(Scalar::from).collect());
+    let proofs = kzg.compute_all_proofs(&p, &domain).unwrap();
+    assert_eq!(proofs[5], kzg.open_at(&p, &domain.element(5)).unwrap().1);
+}
+
+#[test]
+fn test_fk20_coset_proofs() {
+    let kzg = Kzg::trusted_setup(16);
+    let domain = EvaluationDomain::new(16).unwrap();
+    let p = Poly::new((0..13u64).map(|i| Scalar::from(7 * i + 1)).collect());
+    let c = kzg.commit(&p).unwrap();
+
+    for l in [1usize, 2, 4, 16].iter() {
+        let proofs = kzg.compute_coset_proofs(&p, &domain, *l).unwrap();
+        assert_eq!(proofs.len(), 16 / l);
+        for (i, proof) in proofs.iter().enumerate() {
+            let points: Vec<(Scalar, Scalar)> = (0..*l)
+                .map(|t| domain.element(i + t * 16 / l))
+                .map(|x| (x, p.eval(&x)))
+                .collect();
+            assert_eq!(*proof, kzg.prove(&p, &points));
+            // verifying needs [Z(tau)]₂ of degree l, that is out of the setup for l=16
+            if *l < 16 {
+                assert!(kzg.verify(&c, &points, proof));
+            }
+        }
+    }
+
+    for l in [0usize, 3, 32].iter() {
+        assert_eq!(
+            kzg.compute_coset_proofs(&p, &domain, *l),
+            Err(KzgError::InvalidCosetSize {
+                size: *l,
+                domain: 16
+            })
+        );
+    }
+}
@@ -0,0 +1,150 @@
+//! This module implements hiding KZG commitments, the polinomial p is committed
+//! together with a random blinding polinomial r as C = [p(tau)]₁ + [gamma·r(tau)]₁,
+//! so the commitment and the openings do not leak information about p
+//! see https://www.iacr.org/archive/asiacrypt2010/6477178/6477178.pdf (section 3.3)
+
+use super::{
+    kzg::{Commitment, Kzg, KzgError, Proof},
+    msm::msm_g1,
+    poly::Poly,
+};
+use bls12_381::{pairing, G1Affine, G2Affine, Scalar};
+use rand::Rng;
+
+/// Opening of a hiding commitment, that carries the evaluation of the blinding
+/// polinomial at the opened point
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HidingProof {
+    /// [q(tau)]₁ + [gamma·q'(tau)]₁, for the quotients q of p and q' of r
+    pub proof: Proof,
+    /// The evaluation r(z) of the blinding polinomial
+    pub blinding_eval: Scalar,
+}
+
+impl Poly {
+    /// Returns a polinomial of degree `degree` with random coefficients, to be used
+    /// as blinding polinomial
+    pub fn random(degree: usize) -> Poly {
+        let mut rng = rand::thread_rng();
+        Poly::new(
+            (0..=degree)
+                .map(|_| Scalar::from_raw([rng.gen(), rng.gen(), rng.gen(), rng.gen()]))
+                .collect(),
+        )
+    }
+}
+
+impl Kzg {
+    fn eval_at_gamma_tau_g1(&self, poly: &Poly) -> Result<Commitment, KzgError> {
+        if self.pow_gamma_tau_g1.is_empty() {
+            return Err(KzgError::HidingNotSupported);
+        }
+        if poly.degree() >= self.pow_gamma_tau_g1.len() {
+            return Err(KzgError::DegreeTooLarge {
+                degree: poly.degree(),
+                max: self.pow_gamma_tau_g1.len() - 1,
+            });
+        }
+        Ok(msm_g1(&self.pow_gamma_tau_g1, &poly.0))
+    }
+
+    /// Computes the hiding commitment of `poly` blinded with `blinding`, that should
+    /// be a random polinomial with the same degree than poly, see `Poly::random`
+    /// # Example
+    /// ```
+    /// use a0kzg::{Kzg, Poly, Scalar};
+    /// let kzg = Kzg::trusted_setup(4);
+    /// let p = Poly::from(&[1, 2, 3, 4]);
+    /// let r = Poly::random(p.degree());
+    /// let c = kzg.commit_hiding(&p, &r).unwrap();
+    /// assert_ne!(c, kzg.commit(&p).unwrap());
+    ///
+    /// let z = Scalar::from(5);
+    /// let (y, proof) = kzg.open_hiding_at(&p, &r, &z).unwrap();
+    /// assert_eq!(y, p.eval(&z));
+    /// assert!(kzg.verify_hiding_eval(&c, &z, &y, &proof));
+    /// ```
+    pub fn commit_hiding(&self, poly: &Poly, blinding: &Poly) -> Result<Commitment, KzgError> {
+        Ok(self.commit(poly)? + self.eval_at_gamma_tau_g1(blinding)?)
+    }
+
+    /// Opens the hiding commitment of `poly` and `blinding` at `z`, returning
+    /// y=poly(z) and the proof
+    pub fn open_hiding_at(
+        &self,
+        poly: &Poly,
+        blinding: &Poly,
+        z: &Scalar,
+    ) -> Result<(Scalar, HidingProof), KzgError> {
+        let (q, y) = poly.div_by_linear(z);
+        let (q_blinding, blinding_eval) = blinding.div_by_linear(z);
+        let proof = self.commit(&q)? + self.eval_at_gamma_tau_g1(&q_blinding)?;
+        Ok((
+            y,
+            HidingProof {
+                proof,
+                blinding_eval,
+            },
+        ))
+    }
+
+    /// Verifies that the polinomial hidden in `commitment` evaluates to `y` at `z`, by
+    /// checking e(proof, [tau - z]₂) = e(commitment - [y]₁ - [gamma·r(z)]₁, [1]₂)
+    pub fn verify_hiding_eval(
+        &self,
+        commitment: &Commitment,
+        z: &Scalar,
+        y: &Scalar,
+        proof: &HidingProof,
+    ) -> bool {
+        let (tau_g2, gamma_g1) = match (self.pow_tau_g2.get(1), self.pow_gamma_tau_g1.first()) {
+            (Some(tau_g2), Some(gamma_g1)) => (tau_g2, gamma_g1),
+            _ => return false,
+        };
+        let tau_minus_z_g2 = tau_g2 - G2Affine::generator() * z;
+        let e1 = pairing(&proof.proof.into(), &tau_minus_z_g2.into());
+        let e2 = pairing(
+            &(commitment - G1Affine::generator() * y - gamma_g1 * proof.blinding_eval).into(),
+            &G2Affine::generator(),
+        );
+        e1 == e2
+    }
+}
+
+#[test]
+fn test_hiding() {
+    let kzg = Kzg::trusted_setup(8);
+    let p = Poly::from(&[3, 1, 4, 1, 5, 9, 2, 6]);
+    let r = Poly::random(p.degree());
+    let c = kzg.commit_hiding(&p, &r).unwrap();
+
+    // two commitments to the same polinomial are different
+    assert_ne!(c, kzg.commit_hiding(&p, &Poly::random(p.degree())).unwrap());
+
+    for z in [Scalar::zero(), Scalar::from(11), -Scalar::from(2)].iter() {
+        let (y, proof) = kzg.open_hiding_at(&p, &r, z).unwrap();
+        assert_eq!(y, p.eval(z));
+        assert_eq!(proof.blinding_eval, r.eval(z));
+        assert!(kzg.verify_hiding_eval(&c, z, &y, &proof));
+        assert!(!kzg.verify_hiding_eval(&c, z, &(y + Scalar::one()), &proof));
+        assert!(!kzg.verify_hiding_eval(&c, &(z + Scalar::one()), &y, &proof));
+        let bad = HidingProof {
+            blinding_eval: proof.blinding_eval + Scalar::one(),
+            ..proof
+        };
+        assert!(!kzg.verify_hiding_eval(&c, z, &y, &bad));
+    }
+
+    assert_eq!(
+        kzg.commit_hiding(&p, &Poly::random(8)),
+        Err(KzgError::DegreeTooLarge { degree: 8, max: 7 })
+    );
+    let no_hiding = Kzg {
+        pow_gamma_tau_g1: Vec::new(),
+        ..kzg
+    };
+    assert_eq!(
+        no_hiding.commit_hiding(&p, &r),
+        Err(KzgError::HidingNotSupported)
+    );
+}
@@ -0,0 +1,291 @@
+//! This module implements Pedersen vector commitments over Bandersnatch with an
+//! inner product argument to open them. The committed vector is the evaluation form
+//! of a polinomial over the domain {0, 1, ... n-1}, and opening at z proves
+//! <a, b> = y being b the Lagrange coefficients of z. It needs no trusted setup, but
+//! proofs have log(n) points and verifying is linear in n
+//!
+//! The commitments are the ones specified for Ethereum Verkle trees, but the
+//! openings absorb the statement with this crate's `Transcript` framing, so they do
+//! not interoperate with the Ethereum IPA proofs and multiproofs
+//! see https://eprint.iacr.org/2017/1066.pdf (section 3)
+//! see https://dankradfeist.de/ethereum/2021/07/27/inner-product-arguments.html
+
+use super::{
+    bandersnatch::{msm_bandersnatch, pedersen_basis, BandersnatchPoint, BandersnatchScalar},
+    kzg::KzgError,
+    transcript::{Transcript, TranscriptBackend},
+};
+use ff::Field;
+
+/// Pedersen commitments over Bandersnatch with a deterministic basis
+pub struct Ipa {
+    /// The points G_i that the values are committed with, Σ a_i·G_i
+    pub basis: Vec<BandersnatchPoint>,
+    /// The point that the inner product is committed with
+    pub q: BandersnatchPoint,
+    /// 1/Π_{j≠i}(i-j), the barycentric weights of the domain
+    weights: Vec<BandersnatchScalar>,
+}
+
+/// Opening proof of an IPA commitment
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IpaProof {
+    /// The cross commitments L of each round
+    pub l: Vec<BandersnatchPoint>,
+    /// The cross commitments R of each round
+    pub r: Vec<BandersnatchPoint>,
+    /// The committed vector folded to a single value
+    pub a: BandersnatchScalar,
+}
+
+impl Ipa {
+    /// Creates the commitment scheme for vectors of `n` values, rounded up to a power
+    /// of two, with the first n points of `pedersen_basis`
+    /// # Example
+    /// ```
+    /// use a0kzg::{BandersnatchScalar, Ipa, Sha256Transcript};
+    /// let ipa = Ipa::new(8);
+    /// let values: Vec<_> = (1..=8u64).map(BandersnatchScalar::from).collect();
+    /// let c = ipa.commit(&values).unwrap();
+    ///
+    /// let z = BandersnatchScalar::from(100);
+    /// let (y, proof) = ipa
+    ///     .open_at(&values, &z, &mut Sha256Transcript::new(b"example"))
+    ///     .unwrap();
+    /// // the values are the evaluations of p(x) = x + 1
+    /// assert_eq!(y, BandersnatchScalar::from(101));
+    /// assert!(ipa.verify(&c, &z, &y, &proof, &mut Sha256Transcript::new(b"example")));
+    /// ```
+    pub fn new(n: usize) -> Self {
+        let n = n.max(1).next_power_of_two();
+        let weights = (0..n as u64)
+            .map(|i| {
+                (0..n as u64)
+                    .filter(|j| *j != i)
+                    .map(|j| BandersnatchScalar::from(i) - BandersnatchScalar::from(j))
+                    .fold(BandersnatchScalar::ONE, |acc, d| acc * d)
+                    .invert()
+                    .unwrap()
+            })
+            .collect();
+        Self {
+            basis: pedersen_basis(n),
+            q: BandersnatchPoint::generator(),
+            weights,
+        }
+    }
+
+    /// Returns the number of values that can be committed
+    pub fn size(&self) -> usize {
+        self.basis.len()
+    }
+
+    /// Computes Σ values_i·G_i, missing values are zero
+    pub fn commit(&self, values: &[BandersnatchScalar]) -> Result<BandersnatchPoint, KzgError> {
+        self.check_len(values)?;
+        Ok(msm_bandersnatch(&self.basis, values))
+    }
+
+    /// Opens the commitment of `values` at `z`, that can be out of the domain,
+    /// returning y=p(z) and the proof. The commitment, z and y are absorbed in
+    /// `transcript` before the proof
+    pub fn open_at<B: TranscriptBackend>(
+        &self,
+        values: &[BandersnatchScalar],
+        z: &BandersnatchScalar,
+        transcript: &mut Transcript<B>,
+    ) -> Result<(BandersnatchScalar, IpaProof), KzgError> {
+        let commitment = self.commit(values)?;
+        let mut a = values.to_vec();
+        a.resize(self.size(), BandersnatchScalar::ZERO);
+        let mut b = self.lagrange_coeffs(z);
+        let y = inner_product(&a, &b);
+        let q = self.absorb_statement(transcript, &commitment, z, &y);
+
+        // each round halves the vectors, a' = a_lo + x·a_hi, b' = b_lo + x⁻¹·b_hi and
+        // G' = G_lo + x⁻¹·G_hi, so <a', G'> + <a', b'>·Q is C + x·L + x⁻¹·R
+        let mut g = self.basis.clone();
+        let (mut l, mut r) = (Vec::new(), Vec::new());
+        while a.len() > 1 {
+            let m = a.len() / 2;
+            let (a_lo, a_hi) = a.split_at(m);
+            let (b_lo, b_hi) = b.split_at(m);
+            let (g_lo, g_hi) = g.split_at(m);
+            let c_l = msm_bandersnatch(g_lo, a_hi) + q * inner_product(a_hi, b_lo);
+            let c_r = msm_bandersnatch(g_hi, a_lo) + q * inner_product(a_lo, b_hi);
+            transcript.append_bandersnatch_point(b"L", &c_l);
+            transcript.append_bandersnatch_point(b"R", &c_r);
+            let x = transcript.challenge_bandersnatch_scalar(b"x");
+            let x_inv = x.invert().unwrap();
+
+            a = fold(a_lo, a_hi, &x);
+            b = fold(b_lo, b_hi, &x_inv);
+            g = g_lo
+                .iter()
+                .zip(g_hi)
+                .map(|(lo, hi)| *lo + *hi * x_inv)
+                .collect();
+            l.push(c_l);
+            r.push(c_r);
+        }
+        Ok((y, IpaProof { l, r, a: a[0] }))
+    }
+
+    /// Verifies that the vector committed in `commitment` evaluates to `y` at `z`, the
+    /// transcript should be in the same state than the prover's one
+    pub fn verify<B: TranscriptBackend>(
+        &self,
+        commitment: &BandersnatchPoint,
+        z: &BandersnatchScalar,
+        y: &BandersnatchScalar,
+        proof: &IpaProof,
+        transcript: &mut Transcript<B>,
+    ) -> bool {
+        let rounds = self.size().trailing_zeros() as usize;
+        if proof.l.len() != rounds || proof.r.len() != rounds {
+            return false;
+        }
+        let q = self.absorb_statement(transcript, commitment, z, y);
+
+        let mut c = *commitment + q * y;
+        let mut x_invs = Vec::with_capacity(rounds);
+        for (c_l, c_r) in proof.l.iter().zip(proof.r.iter()) {
+            transcript.append_bandersnatch_point(b"L", c_l);
+            transcript.append_bandersnatch_point(b"R", c_r);
+            let x = transcript.challenge_bandersnatch_scalar(b"x");
+            let x_inv = match Option::<BandersnatchScalar>::from(x.invert()) {
+                Some(x_inv) => x_inv,
+                None => return false,
+            };
+            c = c + *c_l * x + *c_r * x_inv;
+            x_invs.push(x_inv);
+        }
+
+        // the folded G and b are <s, G> and <s, b>, being s_i the product of the x⁻¹
+        // of the rounds where i was in the high half
+        let s: Vec<BandersnatchScalar> = (0..self.size())
+            .map(|i| {
+                x_invs
+                    .iter()
+                    .enumerate()
+                    .filter(|(k, _)| (i >> (rounds - 1 - k)) & 1 == 1)
+                    .fold(BandersnatchScalar::ONE, |acc, (_, x_inv)| acc * x_inv)
+            })
+            .collect();
+        let g = msm_bandersnatch(&self.basis, &s);
+        let b = inner_product(&self.lagrange_coeffs(z), &s);
+        c == (g + q * b) * proof.a
+    }
+
+    /// Absorbs the commitment, the point and the evaluation, and returns the point
+    /// that the inner products are committed with, w·Q for a challenge w
+    fn absorb_statement<B: TranscriptBackend>(
+        &self,
+        transcript: &mut Transcript<B>,
+        commitment: &BandersnatchPoint,
+        z: &BandersnatchScalar,
+        y: &BandersnatchScalar,
+    ) -> BandersnatchPoint {
+        transcript.append_message(b"dom-sep", b"ipa");
+        transcript.append_bandersnatch_point(b"C", commitment);
+        transcript.append_bandersnatch_scalar(b"input point", z);
+        transcript.append_bandersnatch_scalar(b"output point", y);
+        self.q * transcript.challenge_bandersnatch_scalar(b"w")
+    }
+
+    /// Returns L_i(z) for each i of the domain, with the barycentric formula
+    /// L_i(z) = A(z)·w_i/(z-i) being A(z) = Π(z-j)
+    fn lagrange_coeffs(&self, z: &BandersnatchScalar) -> Vec<BandersnatchScalar> {
+        let n = self.size();
+        let diffs: Vec<BandersnatchScalar> = (0..n as u64)
+            .map(|i| *z - BandersnatchScalar::from(i))
+            .collect();
+        if let Some(i) = diffs.iter().position(|d| bool::from(d.is_zero())) {
+            let mut coeffs = vec![BandersnatchScalar::ZERO; n];
+            coeffs[i] = BandersnatchScalar::ONE;
+            return coeffs;
+        }
+        let a_z = diffs.iter().fold(BandersnatchScalar::ONE, |acc, d| acc * d);
+        diffs
+            .iter()
+            .zip(self.weights.iter())
+            .map(|(d, w)| a_z * w * d.invert().unwrap())
+            .collect()
+    }
+
+    fn check_len(&self, values: &[BandersnatchScalar]) -> Result<(), KzgError> {
+        if values.len() > self.size() {
+            return Err(KzgError::DegreeTooLarge {
+                degree: values.len() - 1,
+                max: self.size() - 1,
+            });
+        }
+        Ok(())
+    }
+}
+
+fn inner_product(a: &[BandersnatchScalar], b: &[BandersnatchScalar]) -> BandersnatchScalar {
+    a.iter()
+        .zip(b)
+        .fold(BandersnatchScalar::ZERO, |acc, (a, b)| acc + *a * b)
+}
+
+/// Returns lo + k·hi
+fn fold(
+    lo: &[BandersnatchScalar],
+    hi: &[BandersnatchScalar],
+    k: &BandersnatchScalar,
+) -> Vec<BandersnatchScalar> {
+    lo.iter().zip(hi).map(|(lo, hi)| *lo + *hi * k).collect()
+}
+
+#[test]
+fn test_ipa() {
+    use super::transcript::Sha256Transcript;
+
+    let k = |n: u64| BandersnatchScalar::from(n);
+    let ipa = Ipa::new(16);
+    assert_eq!(ipa.size(), 16);
+    // p(x) = x² + 3, with the last values missing
+    let values: Vec<BandersnatchScalar> = (0..13u64).map(|i| k(i * i + 3)).collect();
+    let c = ipa.commit(&values).unwrap();
+    let transcript = || Sha256Transcript::new(b"test");
+
+    for z in [k(0), k(5), k(12), k(1000), -k(7)].iter() {
+        let (y, proof) = ipa.open_at(&values, z, &mut transcript()).unwrap();
+        if *z == k(0) || *z == k(5) || *z == k(12) {
+            assert_eq!(y, *z * z + k(3));
+        }
+        assert_eq!(proof.l.len(), 4);
+        assert!(ipa.verify(&c, z, &y, &proof, &mut transcript()));
+        assert!(!ipa.verify(&c, z, &(y + k(1)), &proof, &mut transcript()));
+        assert!(!ipa.verify(&c, &(*z + k(1)), &y, &proof, &mut transcript()));
+        assert!(!ipa.verify(&(c + ipa.q), z, &y, &proof, &mut transcript()));
+        assert!(!ipa.verify(&c, z, &y, &proof, &mut Sha256Transcript::new(b"other")));
+        let mut bad = proof.clone();
+        bad.a += k(1);
+        assert!(!ipa.verify(&c, z, &y, &bad, &mut transcript()));
+        let mut bad = proof.clone();
+        bad.l.swap(0, 1);
+        assert!(!ipa.verify(&c, z, &y, &bad, &mut transcript()));
+        bad.l.pop();
+        assert!(!ipa.verify(&c, z, &y, &bad, &mut transcript()));
+    }
+
+    assert_eq!(
+        ipa.commit(&[k(1); 17]),
+        Err(KzgError::DegreeTooLarge {
+            degree: 16,
+            max: 15
+        })
+    );
+
+    // the vector commitments of Verkle nodes
+    let ipa = Ipa::new(256);
+    let values: Vec<BandersnatchScalar> = (0..256u64).map(|i| k(i * 7 + 1)).collect();
+    let c = ipa.commit(&values).unwrap();
+    let z = k(300);
+    let (y, proof) = ipa.open_at(&values, &z, &mut transcript()).unwrap();
+    assert_eq!(y, k(300 * 7 + 1));
+    assert!(ipa.verify(&c, &z, &y, &proof, &mut transcript()));
+}
@@ -1,41 +1,113 @@
 //! this module contains an implementation of Kate-Zaverucha-Goldberg polynomial commitments
 
-use super::poly::Poly;
+use super::{
+    domain::powers,
+    msm::{msm_g1, msm_g2},
+    poly::Poly,
+    subproduct::SubproductTree,
+    transcript::Sha256Transcript,
+};
 use bls12_381::*;
 use rand::Rng;
 
 /// KZG polinomial commitments on Bls12-381. This structure contains the trusted setup.
 pub struct Kzg {
-    pub pow_tau_g1: Vec<G1Projective>,
-    pub pow_tau_g2: Vec<G2Projective>,
+    pub pow_tau_g1: Vec<G1Affine>,
+    pub pow_tau_g2: Vec<G2Affine>,
+    /// [L_i(tau)]₁ for the Lagrange basis over the domain of size pow_tau_g1.len(), in
+    /// natural order. Empty if the setup does not include it
+    pub lagrange_g1: Vec<G1Affine>,
+    /// [gamma·tau^i]₁ for a secret gamma, used to blind hiding commitments. Empty if
+    /// the setup does not include it
+    pub pow_gamma_tau_g1: Vec<G1Affine>,
 }
 
 pub type Proof = G1Projective;
 pub type Commitment = G1Projective;
 
+/// Errors that can happen when committing, proving or verifying
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KzgError {
+    /// The polinomial has a larger degree than the one supported by the setup
+    DegreeTooLarge { degree: usize, max: usize },
+    /// Some of the points to prove are not in the polinomial
+    PointNotOnPolynomial,
+    /// Two points to prove or verify have the same x
+    DuplicateX,
+    /// The bytes are not the canonical encoding of a field element
+    InvalidScalar,
+    /// The bytes are not a valid compressed point of the prime order subgroup
+    InvalidPoint,
+    /// The lists of inputs have different lengths
+    LengthMismatch,
+    /// The setup does not include the powers needed for hiding commitments
+    HidingNotSupported,
+    /// The size of the cosets is not a power of two that divides the domain
+    InvalidCosetSize { size: usize, domain: usize },
+    /// The setup has not enough powers of tau in G2 to verify the degree bound, the
+    /// smallest bound that can be verified is `min`
+    DegreeBoundTooSmall { degree_bound: usize, min: usize },
+}
+
+impl std::fmt::Display for KzgError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KzgError::DegreeTooLarge { degree, max } => write!(
+                f,
+                "polinomial degree {} exceeds the setup maximum {}",
+                degree, max
+            ),
+            KzgError::PointNotOnPolynomial => write!(f, "point not on the polinomial"),
+            KzgError::DuplicateX => write!(f, "points with duplicated x"),
+            KzgError::InvalidScalar => write!(f, "invalid field element"),
+            KzgError::InvalidPoint => write!(f, "invalid curve point"),
+            KzgError::LengthMismatch => write!(f, "inputs with different lengths"),
+            KzgError::HidingNotSupported => write!(f, "setup without hiding powers"),
+            KzgError::InvalidCosetSize { size, domain } => write!(
+                f,
+                "coset size {} does not divide the domain size {}",
+                size, domain
+            ),
+            KzgError::DegreeBoundTooSmall { degree_bound, min } => write!(
+                f,
+                "degree bound {} is below the minimum {} supported by the setup",
+                degree_bound, min
+            ),
+        }
+    }
+}
+
+impl std::error::Error for KzgError {}
+
 impl Kzg {
-    fn eval_at_tau_g1(&self, poly: &Poly) -> G1Projective {
-        poly.0
-            .iter()
-            .enumerate()
-            .fold(G1Projective::identity(), |acc, (n, k)| {
-                acc + self.pow_tau_g1[n] * k
-            })
+    fn eval_at_tau_g1(&self, poly: &Poly) -> Result<G1Projective, KzgError> {
+        Self::check_degree(poly, self.pow_tau_g1.len())?;
+        Ok(msm_g1(&self.pow_tau_g1, &poly.0))
     }
 
-    fn eval_at_tau_g2(&self, poly: &Poly) -> G2Projective {
-        poly.0
-            .iter()
-            .enumerate()
-            .fold(G2Projective::identity(), |acc, (n, k)| {
-                acc + self.pow_tau_g2[n] * k
+    fn eval_at_tau_g2(&self, poly: &Poly) -> Result<G2Projective, KzgError> {
+        Self::check_degree(poly, self.pow_tau_g2.len())?;
+        Ok(msm_g2(&self.pow_tau_g2, &poly.0))
+    }
+
+    pub(crate) fn check_degree(poly: &Poly, powers: usize) -> Result<(), KzgError> {
+        if poly.degree() >= powers {
+            Err(KzgError::DegreeTooLarge {
+                degree: poly.degree(),
+                max: powers.saturating_sub(1),
             })
+        } else {
+            Ok(())
+        }
     }
 
-    fn z_poly_of(points: &[(Scalar, Scalar)]) -> Poly {
-        points.iter().fold(Poly::one(), |acc, (z, _y)| {
-            &acc * &Poly::new(vec![-z, Scalar::one()])
-        })
+    /// Returns the polinomial that vanishes in the x of all the `points`
+    pub(crate) fn z_poly_of(points: &[(Scalar, Scalar)]) -> Poly {
+        if points.is_empty() {
+            return Poly::one();
+        }
+        let xs: Vec<Scalar> = points.iter().map(|(x, _)| *x).collect();
+        SubproductTree::new(&xs).root().clone()
     }
 
     /// Generate the trusted setup. Is expected that this function is called
@@ -46,29 +118,35 @@ impl Kzg {
         let rnd: [u64; 4] = [rng.gen(), rng.gen(), rng.gen(), rng.gen()];
         let tau = Scalar::from_raw(rnd);
 
-        let pow_tau_g1: Vec<G1Projective> = (0..n)
-            .into_iter()
-            .scan(Scalar::one(), |acc, _| {
-                let v = *acc;
-                *acc *= tau;
-                Some(v)
-            })
+        let pow_tau = powers(tau, n);
+        let gamma = Scalar::from_raw([rng.gen(), rng.gen(), rng.gen(), rng.gen()]);
+
+        let pow_tau_g1: Vec<G1Projective> = pow_tau
+            .iter()
             .map(|tau_pow| G1Affine::generator() * tau_pow)
             .collect();
+        let mut pow_tau_g1_affine = vec![G1Affine::identity(); n];
+        G1Projective::batch_normalize(&pow_tau_g1, &mut pow_tau_g1_affine);
 
-        let pow_tau_g2: Vec<G2Projective> = (0..n)
-            .into_iter()
-            .scan(Scalar::one(), |acc, _| {
-                let v = *acc;
-                *acc *= tau;
-                Some(v)
-            })
+        let pow_tau_g2: Vec<G2Projective> = pow_tau
+            .iter()
             .map(|tau_pow| G2Affine::generator() * tau_pow)
             .collect();
+        let mut pow_tau_g2_affine = vec![G2Affine::identity(); n];
+        G2Projective::batch_normalize(&pow_tau_g2, &mut pow_tau_g2_affine);
+
+        let pow_gamma_tau_g1: Vec<G1Projective> = pow_tau
+            .iter()
+            .map(|tau_pow| G1Affine::generator() * (gamma * tau_pow))
+            .collect();
+        let mut pow_gamma_tau_g1_affine = vec![G1Affine::identity(); n];
+        G1Projective::batch_normalize(&pow_gamma_tau_g1, &mut pow_gamma_tau_g1_affine);
 
         Self {
-            pow_tau_g1,
-            pow_tau_g2,
+            pow_tau_g1: pow_tau_g1_affine,
+            pow_tau_g2: pow_tau_g2_affine,
+            lagrange_g1: Vec::new(),
+            pow_gamma_tau_g1: pow_gamma_tau_g1_affine,
         }
     }
 
@@ -80,19 +158,40 @@ impl Kzg {
     /// Generate a polinomial and its commitment from a `set` of points
     #[allow(non_snake_case)]
     pub fn poly_commitment_from_set(&self, set: &[(Scalar, Scalar)]) -> (Poly, Commitment) {
-        let poly = Poly::lagrange(set);
-        let commitment = self.eval_at_tau_g1(&poly);
+        let poly = Poly::interpolate_fast(set);
+        let commitment = self.commit(&poly).unwrap();
 
         (poly, commitment)
     }
 
+    /// Computes the commitment of `poly`, that is, evaluating it at tau in G1
+    /// # Example
+    /// ```
+    /// use a0kzg::{Kzg, KzgError, Poly};
+    /// let kzg = Kzg::trusted_setup(3);
+    /// assert!(kzg.commit(&Poly::from(&[1, 2, 3])).is_ok());
+    /// assert_eq!(
+    ///     kzg.commit(&Poly::from(&[1, 2, 3, 4])),
+    ///     Err(KzgError::DegreeTooLarge { degree: 3, max: 2 })
+    /// );
+    /// ```
+    pub fn commit(&self, poly: &Poly) -> Result<Commitment, KzgError> {
+        self.eval_at_tau_g1(poly)
+    }
+
     /// Generates a proof that `points` exists in `set`
-    #[allow(non_snake_case)]
     pub fn prove(&self, poly: &Poly, points: &[(Scalar, Scalar)]) -> Proof {
+        self.try_prove(poly, points).unwrap()
+    }
+
+    /// Generates a proof that `points` exists in `set`, failing if the points are
+    /// not in the polinomial or the polinomial is too large for the setup
+    #[allow(non_snake_case)]
+    pub fn try_prove(&self, poly: &Poly, points: &[(Scalar, Scalar)]) -> Result<Proof, KzgError> {
         // compute a lagrange poliomial I that have all the points to proof that are in the set
         // compute the polinomial Z that has roots (y=0) in all x's of I,
         //   so this is I=(x-x0)(x-x1)...(x-xn)
-        let I = Poly::lagrange(points);
+        let I = Poly::try_interpolate_fast(points).map_err(|_| KzgError::DuplicateX)?;
         let Z = Self::z_poly_of(points);
 
         // now compute that Q = ( P - I(x) ) / Z(x)
@@ -100,7 +199,9 @@ impl Kzg {
         let mut poly = poly.clone();
         poly -= &I;
         let (Q, remainder) = poly / Z;
-        assert!(remainder.is_zero());
+        if !remainder.is_zero() {
+            return Err(KzgError::PointNotOnPolynomial);
+        }
 
         // the proof is evaluating the Q at tau in G1
         self.eval_at_tau_g1(&Q)
@@ -137,23 +238,357 @@ impl Kzg {
     /// let proof0123 = kzg.prove(&p, &set);
     /// assert!(kzg.verify(&c, &set, &proof0123));
     /// ```
-
-    #[allow(non_snake_case)]
     pub fn verify(
         &self,
         commitment: &G1Projective,
         points: &[(Scalar, Scalar)],
         proof: &G1Projective,
     ) -> bool {
-        let I = Poly::lagrange(points);
+        self.try_verify(commitment, points, proof).unwrap_or(false)
+    }
+
+    /// Verifies that `points` exists in `proof`, failing if the points cannot be
+    /// checked with this setup
+    #[allow(non_snake_case)]
+    pub fn try_verify(
+        &self,
+        commitment: &G1Projective,
+        points: &[(Scalar, Scalar)],
+        proof: &G1Projective,
+    ) -> Result<bool, KzgError> {
+        let I = Poly::try_interpolate_fast(points).map_err(|_| KzgError::DuplicateX)?;
         let Z = Self::z_poly_of(points);
 
-        let e1 = pairing(&proof.into(), &self.eval_at_tau_g2(&Z).into());
+        let e1 = pairing(&proof.into(), &self.eval_at_tau_g2(&Z)?.into());
 
         let e2 = pairing(
-            &(commitment - self.eval_at_tau_g1(&I)).into(),
+            &(commitment - self.eval_at_tau_g1(&I)?).into(),
+            &G2Affine::generator(),
+        );
+        Ok(e1 == e2)
+    }
+
+    /// Opens `poly` at `z`, returning y=poly(z) and the proof, that is the commitment to
+    /// the quotient q(x) = (poly(x) - y) / (x - z). Fails if the degree of the
+    /// polinomial is larger than the setup
+    /// # Example
+    /// ```
+    /// use a0kzg::{Kzg, Poly, Scalar};
+    /// let kzg = Kzg::trusted_setup(4);
+    /// let p = Poly::from(&[1, 2, 3, 4]);
+    /// let c = kzg.commit(&p).unwrap();
+    ///
+    /// let z = Scalar::from(5);
+    /// let (y, proof) = kzg.open_at(&p, &z).unwrap();
+    /// assert_eq!(y, p.eval(&z));
+    /// assert!(kzg.verify_eval(&c, &z, &y, &proof));
+    /// assert!(!kzg.verify_eval(&c, &z, &(y + Scalar::one()), &proof));
+    /// ```
+    pub fn open_at(&self, poly: &Poly, z: &Scalar) -> Result<(Scalar, Proof), KzgError> {
+        let (q, y) = poly.div_by_linear(z);
+        Ok((y, self.eval_at_tau_g1(&q)?))
+    }
+
+    /// Verifies that the polinomial committed in `commitment` evaluates to `y` at `z`,
+    /// by checking e(proof, [tau - z]₂) = e(commitment - [y]₁, [1]₂)
+    pub fn verify_eval(
+        &self,
+        commitment: &Commitment,
+        z: &Scalar,
+        y: &Scalar,
+        proof: &Proof,
+    ) -> bool {
+        let tau_g2 = match self.pow_tau_g2.get(1) {
+            Some(tau_g2) => tau_g2,
+            None => return false,
+        };
+        let tau_minus_z_g2 = tau_g2 - G2Affine::generator() * z;
+        let e1 = pairing(&proof.into(), &tau_minus_z_g2.into());
+        let e2 = pairing(
+            &(commitment - G1Affine::generator() * y).into(),
             &G2Affine::generator(),
         );
         e1 == e2
     }
+
+    /// Verifies many `(commitment, z, y, proof)` openings at once, it is false if any
+    /// of them is false for `verify_eval`. The openings are combined with the powers
+    /// of a challenge r derived from all of them, so a single pairing check is done
+    /// # Example
+    /// ```
+    /// use a0kzg::{Kzg, Poly, Scalar};
+    /// let kzg = Kzg::trusted_setup(4);
+    /// let openings: Vec<_> = (1..5u64)
+    ///     .map(|i| {
+    ///         let p = Poly::from(&[i, 2, 3, 4]);
+    ///         let z = Scalar::from(i * 10);
+    ///         let (y, proof) = kzg.open_at(&p, &z).unwrap();
+    ///         (kzg.commit(&p).unwrap(), z, y, proof)
+    ///     })
+    ///     .collect();
+    /// assert!(kzg.verify_batch(&openings));
+    ///
+    /// let mut bad = openings.clone();
+    /// bad[2].2 += Scalar::one();
+    /// assert!(!kzg.verify_batch(&bad));
+    /// ```
+    pub fn verify_batch(&self, openings: &[(Commitment, Scalar, Scalar, Proof)]) -> bool {
+        let mut transcript = Sha256Transcript::new(b"a0kzg-verify-batch");
+        for (commitment, z, y, proof) in openings.iter() {
+            transcript.append_commitment(b"commitment", commitment);
+            transcript.append_scalar(b"z", z);
+            transcript.append_scalar(b"y", y);
+            transcript.append_proof(b"proof", proof);
+        }
+        let r = transcript.challenge_scalar(b"r");
+        self.verify_batch_with(openings, &r)
+    }
+
+    /// Checks e(Σ r^i·proof_i, [tau]₂) = e(Σ r^i·(C_i - [y_i]₁ + z_i·proof_i), [1]₂)
+    /// with a multi miller loop and a single final exponentiation
+    pub(crate) fn verify_batch_with(
+        &self,
+        openings: &[(Commitment, Scalar, Scalar, Proof)],
+        r: &Scalar,
+    ) -> bool {
+        let tau_g2 = match self.pow_tau_g2.get(1) {
+            Some(tau_g2) => tau_g2,
+            None => return false,
+        };
+
+        // bases are [C_0 .. C_n-1, proof_0 .. proof_n-1]
+        let n = openings.len();
+        let r_pows = powers(*r, n);
+        let mut bases = vec![G1Projective::identity(); 2 * n];
+        let mut scalars = vec![Scalar::zero(); 2 * n];
+        let mut y_lincomb = Scalar::zero();
+        for (i, ((c, z, y, proof), r_pow)) in openings.iter().zip(r_pows.iter()).enumerate() {
+            bases[i] = *c;
+            bases[n + i] = *proof;
+            scalars[i] = *r_pow;
+            scalars[n + i] = r_pow * z;
+            y_lincomb += r_pow * y;
+        }
+        let mut bases_affine = vec![G1Affine::identity(); 2 * n];
+        G1Projective::batch_normalize(&bases, &mut bases_affine);
+
+        let proof_lincomb = msm_g1(&bases_affine[n..], &r_pows);
+        let rhs_lincomb = msm_g1(&bases_affine, &scalars) - G1Affine::generator() * y_lincomb;
+
+        let result = multi_miller_loop(&[
+            (&proof_lincomb.into(), &G2Prepared::from(-tau_g2)),
+            (
+                &rhs_lincomb.into(),
+                &G2Prepared::from(G2Affine::generator()),
+            ),
+        ])
+        .final_exponentiation();
+        result == Gt::identity()
+    }
+
+    /// Opens all the `polys` at the same point `z` with a single proof (GWC19). The
+    /// polinomials are combined as Σ challenge^i·polys_i and the combination is
+    /// opened, `challenge` should be derived from a transcript after absorbing the
+    /// commitments, z and the evaluations. Returns the evaluations polys_i(z) and the
+    /// proof. Fails if the degree of any polinomial is larger than the setup
+    /// see https://eprint.iacr.org/2019/953.pdf
+    /// # Example
+    /// ```
+    /// use a0kzg::{Kzg, Poly, Scalar, Sha256Transcript};
+    /// let kzg = Kzg::trusted_setup(4);
+    /// let (p0, p1) = (Poly::from(&[1, 2, 3]), Poly::from(&[4, 5, 6, 7]));
+    /// let commitments = vec![kzg.commit(&p0).unwrap(), kzg.commit(&p1).unwrap()];
+    /// let z = Scalar::from(9);
+    ///
+    /// let ys = vec![p0.eval(&z), p1.eval(&z)];
+    /// let mut transcript = Sha256Transcript::new(b"example");
+    /// for (c, y) in commitments.iter().zip(ys.iter()) {
+    ///     transcript.append_commitment(b"c", c);
+    ///     transcript.append_scalar(b"y", y);
+    /// }
+    /// transcript.append_scalar(b"z", &z);
+    /// let v = transcript.challenge_scalar(b"v");
+    ///
+    /// let (ys, proof) = kzg.open_many_polys_at(&[&p0, &p1], &z, &v).unwrap();
+    /// assert!(kzg.verify_many_polys_at(&commitments, &z, &ys, &v, &proof));
+    /// ```
+    pub fn open_many_polys_at(
+        &self,
+        polys: &[&Poly],
+        z: &Scalar,
+        challenge: &Scalar,
+    ) -> Result<(Vec<Scalar>, Proof), KzgError> {
+        let ys = polys.iter().map(|p| p.eval(z)).collect();
+        let mut combined = Poly::zero();
+        for (p, v_pow) in polys.iter().zip(powers(*challenge, polys.len())) {
+            combined += &(*p * &v_pow);
+        }
+        let (_, proof) = self.open_at(&combined, z)?;
+        Ok((ys, proof))
+    }
+
+    /// Verifies a proof generated with `open_many_polys_at`, the commitments and
+    /// evaluations are combined in the same way the polinomials were, and the result
+    /// is checked with `verify_eval`
+    pub fn verify_many_polys_at(
+        &self,
+        commitments: &[Commitment],
+        z: &Scalar,
+        ys: &[Scalar],
+        challenge: &Scalar,
+        proof: &Proof,
+    ) -> bool {
+        if commitments.len() != ys.len() {
+            return false;
+        }
+        let v_pows = powers(*challenge, ys.len());
+        let mut commitments_affine = vec![G1Affine::identity(); commitments.len()];
+        G1Projective::batch_normalize(commitments, &mut commitments_affine);
+        let commitment = msm_g1(&commitments_affine, &v_pows);
+        let y = ys
+            .iter()
+            .zip(v_pows.iter())
+            .fold(Scalar::zero(), |acc, (y, v_pow)| acc + y * v_pow);
+        self.verify_eval(&commitment, z, &y, proof)
+    }
+}
+
+#[test]
+fn test_kzg_errors() {
+    let kzg = Kzg::trusted_setup(5);
+    let set = vec![
+        (Scalar::from(1), Scalar::from(2)),
+        (Scalar::from(2), Scalar::from(3)),
+        (Scalar::from(3), Scalar::from(4)),
+        (Scalar::from(4), Scalar::from(57)),
+    ];
+    let (p, c) = kzg.poly_commitment_from_set(&set);
+
+    assert_eq!(
+        kzg.commit(&Poly::from(&[1, 2, 3, 4, 5, 6])),
+        Err(KzgError::DegreeTooLarge { degree: 5, max: 4 })
+    );
+    let empty = Kzg {
+        pow_tau_g1: Vec::new(),
+        pow_tau_g2: Vec::new(),
+        lagrange_g1: Vec::new(),
+        pow_gamma_tau_g1: Vec::new(),
+    };
+    assert_eq!(
+        empty.commit(&Poly::from(&[1])),
+        Err(KzgError::DegreeTooLarge { degree: 0, max: 0 })
+    );
+    assert_eq!(
+        kzg.try_prove(&p, &[(Scalar::from(1), Scalar::from(3))]),
+        Err(KzgError::PointNotOnPolynomial)
+    );
+    assert_eq!(
+        kzg.try_prove(&p, &[set[0], set[0]]),
+        Err(KzgError::DuplicateX)
+    );
+
+    let proof = kzg.try_prove(&p, &set[..2]).unwrap();
+    assert_eq!(kzg.try_verify(&c, &set[..2], &proof), Ok(true));
+    assert_eq!(kzg.try_verify(&c, &set[1..3], &proof), Ok(false));
+    assert_eq!(
+        kzg.try_verify(&c, &[set[0], set[0]], &proof),
+        Err(KzgError::DuplicateX)
+    );
+}
+
+#[test]
+fn test_kzg_open_at() {
+    let kzg = Kzg::trusted_setup(33);
+    let p = Poly::new((0..33u64).map(|i| Scalar::from(i * i + 7)).collect());
+    let c = kzg.commit(&p).unwrap();
+
+    for z in [Scalar::zero(), Scalar::from(3), -Scalar::from(99)].iter() {
+        let (y, proof) = kzg.open_at(&p, z).unwrap();
+        assert_eq!(y, p.eval(z));
+        assert!(kzg.verify_eval(&c, z, &y, &proof));
+        assert!(!kzg.verify_eval(&c, &(z + Scalar::one()), &y, &proof));
+        assert!(!kzg.verify_eval(&c, z, &(y + Scalar::one()), &proof));
+        // single point openings are also valid subset proofs
+        assert!(kzg.verify(&c, &[(*z, y)], &proof));
+    }
+
+    let large = Poly::new((0..35u64).map(Scalar::from).collect());
+    assert_eq!(
+        kzg.open_at(&large, &Scalar::from(3)),
+        Err(KzgError::DegreeTooLarge {
+            degree: 33,
+            max: 32
+        })
+    );
+}
+
+#[test]
+fn test_kzg_verify_batch() {
+    let kzg = Kzg::trusted_setup(16);
+    let openings: Vec<(Commitment, Scalar, Scalar, Proof)> = (0..20u64)
+        .map(|i| {
+            let p = Poly::new((0..16).map(|j| Scalar::from(i * 31 + j * j)).collect());
+            let z = Scalar::from(i * 7 + 1);
+            let (y, proof) = kzg.open_at(&p, &z).unwrap();
+            (kzg.commit(&p).unwrap(), z, y, proof)
+        })
+        .collect();
+    assert!(kzg.verify_batch(&openings));
+    assert!(kzg.verify_batch(&openings[..1]));
+    assert!(kzg.verify_batch(&[]));
+
+    // any bad member makes the batch fail
+    for i in [0, 7, 19].iter() {
+        let mut bad = openings.clone();
+        bad[*i].1 += Scalar::one();
+        assert!(!kzg.verify_batch(&bad));
+        let mut bad = openings.clone();
+        bad[*i].3 = openings[(i + 1) % 20].3;
+        assert!(!kzg.verify_batch(&bad));
+    }
+    // swapping two openings keeps them valid
+    let mut swapped = openings.clone();
+    swapped.swap(3, 4);
+    assert!(kzg.verify_batch(&swapped));
+}
+
+#[test]
+fn test_kzg_open_many_polys_at() {
+    let kzg = Kzg::trusted_setup(16);
+    let polys: Vec<Poly> = (0..12u64)
+        .map(|i| Poly::new((0..=i).map(|j| Scalar::from(i * 13 + j + 1)).collect()))
+        .collect();
+    let refs: Vec<&Poly> = polys.iter().collect();
+    let commitments: Vec<Commitment> = polys.iter().map(|p| kzg.commit(p).unwrap()).collect();
+    let (z, v) = (Scalar::from(17), Scalar::from(1234567));
+
+    let (ys, proof) = kzg.open_many_polys_at(&refs, &z, &v).unwrap();
+    for (p, y) in polys.iter().zip(ys.iter()) {
+        assert_eq!(p.eval(&z), *y);
+    }
+    assert!(kzg.verify_many_polys_at(&commitments, &z, &ys, &v, &proof));
+
+    // a wrong evaluation, point, challenge or commitment fails
+    let mut bad_ys = ys.clone();
+    bad_ys[5] += Scalar::one();
+    assert!(!kzg.verify_many_polys_at(&commitments, &z, &bad_ys, &v, &proof));
+    assert!(!kzg.verify_many_polys_at(&commitments, &(z + Scalar::one()), &ys, &v, &proof));
+    assert!(!kzg.verify_many_polys_at(&commitments, &z, &ys, &(v + Scalar::one()), &proof));
+    let mut bad_commitments = commitments.clone();
+    bad_commitments.swap(0, 1);
+    assert!(!kzg.verify_many_polys_at(&bad_commitments, &z, &ys, &v, &proof));
+    assert!(!kzg.verify_many_polys_at(&commitments[1..], &z, &ys, &v, &proof));
+
+    // a single polinomial is the same than open_at
+    let (ys, proof) = kzg.open_many_polys_at(&refs[3..4], &z, &v).unwrap();
+    assert_eq!((ys[0], proof), kzg.open_at(&polys[3], &z).unwrap());
+
+    let large = Poly::new((0..18u64).map(Scalar::from).collect());
+    assert_eq!(
+        kzg.open_many_polys_at(&[&polys[0], &large], &z, &v),
+        Err(KzgError::DegreeTooLarge {
+            degree: 16,
+            max: 15
+        })
+    );
 }
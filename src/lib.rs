@@ -1,6 +1,47 @@
+mod bandersnatch;
+mod degree_bound;
+mod domain;
+mod eip4844;
+mod eip6800;
+mod eval_poly;
+mod fk20;
+mod hiding;
+mod ipa;
 mod kzg;
+mod msm;
 mod poly;
+mod setup;
+mod shplonk;
+mod subproduct;
+mod transcript;
+mod verkle;
+mod verkle_proof;
+mod verkle_store;
 
+pub use bandersnatch::{
+    msm_bandersnatch, pedersen_basis, BandersnatchPoint, BandersnatchScalar, PEDERSEN_BASIS_SEED,
+};
 pub use bls12_381::Scalar;
-pub use kzg::{Commitment, Kzg, Proof};
-pub use poly::Poly;
+pub use domain::{bit_reverse_permutation, EvaluationDomain};
+pub use eip4844::{
+    Blob, Bytes32, Bytes48, BYTES_PER_BLOB, BYTES_PER_COMMITMENT, BYTES_PER_FIELD_ELEMENT,
+    BYTES_PER_PROOF, FIELD_ELEMENTS_PER_BLOB,
+};
+pub use eip6800::{
+    address32, chunkify_code, Address, Address32, BasicData, Eip6800Error, BASIC_DATA_LEAF_KEY,
+    CODE_CHUNK_SIZE, CODE_HASH_LEAF_KEY, CODE_OFFSET, HEADER_STORAGE_OFFSET,
+};
+pub use eval_poly::EvalPoly;
+pub use hiding::HidingProof;
+pub use ipa::{Ipa, IpaProof};
+pub use kzg::{Commitment, Kzg, KzgError, Proof};
+pub use msm::{msm_g1, msm_g2};
+pub use poly::{Poly, PolyError};
+pub use setup::SetupError;
+pub use shplonk::MultiProof;
+pub use transcript::{
+    MerlinBackend, MerlinTranscript, Sha256Backend, Sha256Transcript, Transcript, TranscriptBackend,
+};
+pub use verkle::{Stem, VerkleCommitter, VerkleKey, VerkleTree, VerkleValue, VERKLE_NODE_WIDTH};
+pub use verkle_proof::{verify_verkle_proof, ExtensionStatus, VerkleProof};
+pub use verkle_store::{FileStore, MemoryStore, VerkleNode, VerkleStore};
@@ -0,0 +1,143 @@
+//! This module provides multi-scalar multiplication Σ k_i·P_i with the bucket
+//! method of Pippenger
+//! see https://cr.yp.to/papers/pippenger.pdf
+
+use bls12_381::{G1Affine, G1Projective, G2Affine, G2Projective, Scalar};
+use std::ops::AddAssign;
+
+/// Number of bits of the canonical representation of a scalar
+const SCALAR_BITS: u32 = 255;
+
+/// A group in projective coordinates where affine points can be added
+pub(crate) trait MsmGroup:
+    Copy + Default + AddAssign + for<'a> AddAssign<&'a Self::Affine>
+{
+    type Affine;
+    fn double(&self) -> Self;
+}
+
+impl MsmGroup for G1Projective {
+    type Affine = G1Affine;
+    fn double(&self) -> Self {
+        G1Projective::double(self)
+    }
+}
+
+impl MsmGroup for G2Projective {
+    type Affine = G2Affine;
+    fn double(&self) -> Self {
+        G2Projective::double(self)
+    }
+}
+
+/// Computes Σ scalars_i·bases_i in G1, extra bases or scalars are ignored
+/// # Examples
+/// ```
+///    use a0kzg::{msm_g1, Scalar};
+///    use bls12_381::{G1Affine, G1Projective};
+///    let g = G1Affine::generator();
+///    assert_eq!(
+///      msm_g1(&[g, g], &[Scalar::from(2), Scalar::from(3)]),
+///      G1Projective::generator() * Scalar::from(5)
+///    );
+/// ```
+pub fn msm_g1(bases: &[G1Affine], scalars: &[Scalar]) -> G1Projective {
+    pippenger(bases, scalars)
+}
+
+/// Computes Σ scalars_i·bases_i in G2, extra bases or scalars are ignored
+pub fn msm_g2(bases: &[G2Affine], scalars: &[Scalar]) -> G2Projective {
+    pippenger(bases, scalars)
+}
+
+/// Splits the scalars in windows of c bits, for each window adds each base into
+/// the bucket of its digit and then sums the buckets weighting them by its digit,
+/// finally the windows are combined with doublings
+pub(crate) fn pippenger<P: MsmGroup>(bases: &[P::Affine], scalars: &[Scalar]) -> P {
+    let n = bases.len().min(scalars.len());
+    if n == 0 {
+        return P::default();
+    }
+    let c = window_size(n);
+    let scalars: Vec<[u8; 32]> = scalars[..n].iter().map(|s| s.to_bytes()).collect();
+
+    let windows = SCALAR_BITS.div_ceil(c);
+    let mut acc = P::default();
+    for w in (0..windows).rev() {
+        for _ in 0..c {
+            acc = acc.double();
+        }
+
+        let mut buckets = vec![P::default(); (1 << c) - 1];
+        for (scalar, base) in scalars.iter().zip(bases.iter()) {
+            let digit = window_digit(scalar, w * c, c);
+            if digit != 0 {
+                buckets[digit - 1] += base;
+            }
+        }
+
+        // Σ (i+1)·bucket_i as the sum of the running sums from the top bucket
+        let (mut running, mut window_sum) = (P::default(), P::default());
+        for bucket in buckets.into_iter().rev() {
+            running += bucket;
+            window_sum += running;
+        }
+        acc += window_sum;
+    }
+    acc
+}
+
+/// Number of bits per window that minimizes the number of additions, each window
+/// takes n additions to fill the buckets and 2·2^c to sum them
+fn window_size(n: usize) -> u32 {
+    (1..16)
+        .min_by_key(|c| SCALAR_BITS.div_ceil(*c) as usize * (n + (2 << c)))
+        .unwrap()
+}
+
+/// Returns the `c` bits of the little-endian `scalar` that start at bit `start`
+fn window_digit(scalar: &[u8; 32], start: u32, c: u32) -> usize {
+    let mut digit = 0usize;
+    for i in 0..c {
+        let bit = start + i;
+        if bit >= SCALAR_BITS {
+            break;
+        }
+        let b = (scalar[(bit / 8) as usize] >> (bit % 8)) & 1;
+        digit |= (b as usize) << i;
+    }
+    digit
+}
+
+#[test]
+fn test_msm() {
+    use rand::Rng;
+    let mut rng = rand::thread_rng();
+    for n in [1usize, 7, 33, 200].iter() {
+        let scalars: Vec<Scalar> = (0..*n)
+            .map(|_| Scalar::from_raw([rng.gen(), rng.gen(), rng.gen(), rng.gen()]))
+            .collect();
+        let bases_g1: Vec<G1Affine> = (0..*n)
+            .map(|i| G1Affine::from(G1Affine::generator() * Scalar::from(i as u64 + 3)))
+            .collect();
+        let expected = bases_g1
+            .iter()
+            .zip(scalars.iter())
+            .fold(G1Projective::identity(), |acc, (b, s)| acc + b * s);
+        assert_eq!(msm_g1(&bases_g1, &scalars), expected);
+
+        let bases_g2: Vec<G2Affine> = (0..*n)
+            .map(|i| G2Affine::from(G2Affine::generator() * Scalar::from(i as u64 + 3)))
+            .collect();
+        let expected = bases_g2
+            .iter()
+            .zip(scalars.iter())
+            .fold(G2Projective::identity(), |acc, (b, s)| acc + b * s);
+        assert_eq!(msm_g2(&bases_g2, &scalars), expected);
+    }
+    assert_eq!(msm_g1(&[], &[]), G1Projective::identity());
+    assert_eq!(
+        msm_g1(&[G1Affine::generator()], &[-Scalar::one()]),
+        -G1Projective::generator()
+    );
+}
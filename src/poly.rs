@@ -1,7 +1,28 @@
 //! This module provides an implementation of polinomials over bls12_381::Scalar
 
+use super::domain::EvaluationDomain;
 use bls12_381::Scalar;
 
+/// Errors that can happen when operating with polinomials
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PolyError {
+    /// Two points to interpolate have the same x
+    DuplicateX,
+    /// The divisor is p(x)=0
+    DivisionByZero,
+}
+
+impl std::fmt::Display for PolyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PolyError::DuplicateX => write!(f, "points with duplicated x"),
+            PolyError::DivisionByZero => write!(f, "division by zero"),
+        }
+    }
+}
+
+impl std::error::Error for PolyError {}
+
 /// A polinomial with bl12_381::Scalar factors
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Poly(pub(crate) Vec<Scalar>);
@@ -49,19 +70,37 @@ impl Poly {
     ///    );
     /// ```
     pub fn lagrange(p: &[(Scalar, Scalar)]) -> Self {
+        Self::try_lagrange(p).expect("points with duplicated x")
+    }
+
+    /// Like `lagrange`, but fails with `PolyError::DuplicateX` instead of panicking
+    /// if two points have the same x
+    /// # Examples
+    /// ```
+    ///    use a0kzg::{Poly, PolyError, Scalar};
+    ///    assert_eq!(
+    ///      Poly::try_lagrange(&vec![
+    ///          (Scalar::from(1), Scalar::from(1)),
+    ///          (Scalar::from(1), Scalar::from(2))
+    ///      ]),
+    ///      Err(PolyError::DuplicateX)
+    ///    );
+    /// ```
+    pub fn try_lagrange(p: &[(Scalar, Scalar)]) -> Result<Self, PolyError> {
         let k = p.len();
         let mut l = Poly::zero();
         for j in 0..k {
             let mut l_j = Poly::one();
             for i in 0..k {
                 if i != j {
-                    let c = (p[j].0 - p[i].0).invert().unwrap();
+                    let c: Option<Scalar> = (p[j].0 - p[i].0).invert().into();
+                    let c = c.ok_or(PolyError::DuplicateX)?;
                     l_j = &l_j * &Poly::new(vec![-(c * p[i].0), c]);
                 }
             }
             l += &(&l_j * &p[j].1);
         }
-        l
+        Ok(l)
     }
 
     /// Evals the polinomial at the desired point
@@ -92,6 +131,31 @@ impl Poly {
         y
     }
 
+    /// Divides the polinomial by (x - `z`) using synthetic division, returns the
+    /// quotient and the remainder, that is p(z)
+    /// # Examples
+    /// ```
+    ///    use a0kzg::{Poly, Scalar};
+    ///    // x^2+2x+1 = (x+1)(x-2) + 9
+    ///    let (q, r) = Poly::from(&[1, 2, 1]).div_by_linear(&Scalar::from(2));
+    ///    assert_eq!(q, Poly::from(&[4, 1]));
+    ///    assert_eq!(r, Scalar::from(9));
+    /// ```
+    pub fn div_by_linear(&self, z: &Scalar) -> (Poly, Scalar) {
+        let mut q = vec![Scalar::zero(); self.0.len() - 1];
+        let mut acc = Scalar::zero();
+        for (i, c) in self.0.iter().enumerate().rev() {
+            acc = acc * z + c;
+            if i > 0 {
+                q[i - 1] = acc;
+            }
+        }
+        if q.is_empty() {
+            q.push(Scalar::zero());
+        }
+        (Poly::new(q), acc)
+    }
+
     /// Returns the degree of the polinominal, degree(x+1) = 1
     pub fn degree(&self) -> usize {
         self.0.len() - 1
@@ -230,16 +294,42 @@ impl std::ops::SubAssign<&Poly> for Poly {
 impl std::ops::Mul<&Poly> for &Poly {
     type Output = Poly;
     fn mul(self, rhs: &Poly) -> Self::Output {
-        let mut mul: Vec<Scalar> = std::iter::repeat(Scalar::zero())
-            .take(self.0.len() + rhs.0.len() - 1)
-            .collect();
-        for n in 0..self.0.len() {
-            for m in 0..rhs.0.len() {
-                mul[n + m] += self.0[n] * rhs.0[m];
-            }
+        if self.0.len().min(rhs.0.len()) >= NTT_MUL_THRESHOLD {
+            mul_ntt(self, rhs)
+        } else {
+            mul_schoolbook(self, rhs)
+        }
+    }
+}
+
+/// Minimum number of coefficients that both factors should have to multiply them
+/// with the NTT instead of the schoolbook O(n·m) method
+const NTT_MUL_THRESHOLD: usize = 32;
+
+fn mul_schoolbook(a: &Poly, b: &Poly) -> Poly {
+    let mut mul: Vec<Scalar> = vec![Scalar::zero(); a.0.len() + b.0.len() - 1];
+    for n in 0..a.0.len() {
+        for m in 0..b.0.len() {
+            mul[n + m] += a.0[n] * b.0[m];
         }
-        Poly(mul)
     }
+    Poly::new(mul)
+}
+
+/// Multiplies by evaluating both factors in a domain big enough to hold the
+/// product, multiplying pointwise and interpolating back
+fn mul_ntt(a: &Poly, b: &Poly) -> Poly {
+    let len = a.0.len() + b.0.len() - 1;
+    let domain = EvaluationDomain::new(len).expect("product too large for the NTT");
+    let mut a_evals = domain.fft(a);
+    let b_evals = domain.fft(b);
+    a_evals
+        .iter_mut()
+        .zip(b_evals.iter())
+        .for_each(|(a, b)| *a *= b);
+    domain.ifft_in_place(&mut a_evals);
+    a_evals.truncate(len);
+    Poly::new(a_evals)
 }
 
 impl std::ops::Mul<&Scalar> for &Poly {
@@ -257,19 +347,110 @@ impl std::ops::Div for Poly {
     type Output = (Poly, Poly);
 
     fn div(self, rhs: Poly) -> Self::Output {
-        let (mut q, mut r) = (Poly::zero(), self);
-        while !r.is_zero() && r.degree() >= rhs.degree() {
-            let lead_r = r.0[r.0.len() - 1];
-            let lead_d = rhs.0[rhs.0.len() - 1];
-            let mut t = Poly::zero();
-            t.set(r.0.len() - rhs.0.len(), lead_r * lead_d.invert().unwrap());
-            q += &t;
-            r -= &(&rhs * &t);
+        self.checked_div(&rhs).expect("division by zero")
+    }
+}
+
+impl Poly {
+    /// Divides by `rhs` returning the quotient and the remainder, or
+    /// `PolyError::DivisionByZero` if `rhs` is p(x)=0
+    /// # Examples
+    /// ```
+    ///    use a0kzg::{Poly, PolyError};
+    ///    assert_eq!(
+    ///      Poly::from(&[1, 2, 1]).checked_div(&Poly::from(&[1, 1])),
+    ///      Ok((Poly::from(&[1, 1]), Poly::zero()))
+    ///    );
+    ///    assert_eq!(
+    ///      Poly::from(&[1, 2, 1]).checked_div(&Poly::zero()),
+    ///      Err(PolyError::DivisionByZero)
+    ///    );
+    /// ```
+    pub fn checked_div(&self, rhs: &Poly) -> Result<(Poly, Poly), PolyError> {
+        if rhs.is_zero() {
+            return Err(PolyError::DivisionByZero);
+        }
+        if self.is_zero() || self.degree() < rhs.degree() {
+            return Ok((Poly::zero(), self.clone()));
+        }
+        if rhs.degree() == 1 {
+            // a·x + b = a·(x - z) with z = -b/a
+            let lead_inv = rhs.0[1].invert().unwrap();
+            let (q, r) = self.div_by_linear(&-(rhs.0[0] * lead_inv));
+            return Ok((&q * &lead_inv, Poly::new(vec![r])));
+        }
+        let quotient_len = self.degree() - rhs.degree() + 1;
+        if quotient_len.min(rhs.0.len()) >= NEWTON_DIV_THRESHOLD {
+            Ok(div_newton(self, rhs))
+        } else {
+            Ok(div_schoolbook(self, rhs))
         }
-        (q, r)
     }
 }
 
+/// Minimum number of coefficients that both the divisor and the quotient should
+/// have to divide with Newton iteration instead of long division
+const NEWTON_DIV_THRESHOLD: usize = 128;
+
+/// Long division, in place over the coefficients of the remainder
+fn div_schoolbook(a: &Poly, b: &Poly) -> (Poly, Poly) {
+    let (n, m) = (a.degree(), b.degree());
+    let lead_inv = b.0[m].invert().unwrap();
+    let mut r = a.0.clone();
+    let mut q = vec![Scalar::zero(); n - m + 1];
+    for i in (0..=n - m).rev() {
+        let c = r[i + m] * lead_inv;
+        q[i] = c;
+        for (j, b_j) in b.0.iter().enumerate() {
+            r[i + j] -= c * b_j;
+        }
+    }
+    r.truncate(m.max(1));
+    (Poly::new(q), Poly::new(r))
+}
+
+/// Division using that rev(q) = rev(a) · rev(b)^-1 mod x^(n-m+1), where rev(p) are
+/// the coefficients of p in reverse order and the inverse is computed with Newton
+fn div_newton(a: &Poly, b: &Poly) -> (Poly, Poly) {
+    let (n, m) = (a.degree(), b.degree());
+    let k = n - m + 1;
+    let rev_a = truncated(&reversed(a, n), k);
+    let rev_b_inv = inv_mod_xn(&reversed(b, m), k);
+    let q = reversed(&truncated(&(&rev_a * &rev_b_inv), k), k - 1);
+    let mut r = a.clone();
+    r -= &(b * &q);
+    (q, r)
+}
+
+/// Computes g such that f·g = 1 mod x^n by Newton iteration, g' = g·(2 - f·g),
+/// doubling the number of correct coefficients on each step
+fn inv_mod_xn(f: &Poly, n: usize) -> Poly {
+    let mut g = Poly::new(vec![f.0[0].invert().unwrap()]);
+    let mut l = 1;
+    while l < n {
+        l = (2 * l).min(n);
+        let mut e = truncated(&(&truncated(f, l) * &g), l);
+        e.0.iter_mut().for_each(|c| *c = -*c);
+        e.0[0] += Scalar::from(2);
+        e.normalize();
+        g = truncated(&(&g * &e), l);
+    }
+    g
+}
+
+/// Returns p mod x^n
+fn truncated(p: &Poly, n: usize) -> Poly {
+    Poly::new(p.0[..n.min(p.0.len())].to_vec())
+}
+
+/// Returns x^n·p(1/x), the coefficients of p padded to n+1 and reversed
+fn reversed(p: &Poly, n: usize) -> Poly {
+    let mut coeffs = p.0.clone();
+    coeffs.resize(n + 1, Scalar::zero());
+    coeffs.reverse();
+    Poly::new(coeffs)
+}
+
 #[test]
 fn test_poly_add() {
     let mut p246 = Poly::from(&[1, 2, 3]);
@@ -304,6 +485,27 @@ fn test_poly_mul() {
     );
 }
 
+#[test]
+fn test_poly_mul_ntt() {
+    use rand::Rng;
+    let mut rng = rand::thread_rng();
+    let mut rnd_poly = |n: usize| {
+        Poly::new(
+            (0..n)
+                .map(|_| Scalar::from_raw([rng.gen(), rng.gen(), rng.gen(), rng.gen()]))
+                .collect(),
+        )
+    };
+    for (n, m) in [(32, 32), (33, 100), (256, 257), (1000, 40)].iter() {
+        let (a, b) = (rnd_poly(*n), rnd_poly(*m));
+        assert_eq!(mul_ntt(&a, &b), mul_schoolbook(&a, &b));
+        assert_eq!(&a * &b, mul_schoolbook(&a, &b));
+    }
+    let a = rnd_poly(64);
+    assert_eq!(mul_ntt(&a, &Poly::zero()), Poly::zero());
+    assert_eq!(mul_schoolbook(&a, &Poly::zero()), Poly::zero());
+}
+
 #[test]
 fn test_div() {
     fn do_test(n: Poly, d: Poly) {
@@ -322,6 +524,37 @@ fn test_div() {
     );
 }
 
+#[test]
+fn test_div_fast() {
+    use rand::Rng;
+    let mut rng = rand::thread_rng();
+    let mut rnd_poly = |n: usize| {
+        Poly::new(
+            (0..n)
+                .map(|_| Scalar::from_raw([rng.gen(), rng.gen(), rng.gen(), rng.gen()]))
+                .collect(),
+        )
+    };
+    for (n, m) in [(300, 130), (1024, 128), (2000, 700), (260, 130)].iter() {
+        let (a, b) = (rnd_poly(*n), rnd_poly(*m));
+        let (q, r) = div_newton(&a, &b);
+        assert_eq!((q.clone(), r.clone()), div_schoolbook(&a, &b));
+        assert_eq!((q, r), a.clone() / b.clone());
+    }
+
+    let (a, z) = (rnd_poly(100), Scalar::from(7));
+    let (q, r) = a.div_by_linear(&z);
+    assert_eq!(r, a.eval(&z));
+    assert_eq!(
+        (q, Poly::new(vec![r])),
+        div_schoolbook(&a, &Poly::new(vec![-z, Scalar::one()]))
+    );
+    assert_eq!(
+        Poly::from(&[5]).div_by_linear(&z),
+        (Poly::zero(), Scalar::from(5))
+    );
+}
+
 #[test]
 fn test_print() {
     assert_eq!("x^2+2x+1", format!("{}", Poly::from(&[1, 2, 1])));
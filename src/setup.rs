@@ -0,0 +1,413 @@
+//! This module provides the encoding of the KZG trusted setup, so the same powers
+//! of tau can be shared between provers and verifiers, and the loading of the
+//! Ethereum KZG ceremony output
+
+use super::{domain::EvaluationDomain, kzg::Kzg};
+use bls12_381::{G1Affine, G1Projective, G2Affine};
+use std::{
+    io::{BufRead, Read, Write},
+    path::Path,
+};
+
+/// Magic bytes at the start of an encoded setup
+const MAGIC: &[u8; 5] = b"a0kzg";
+/// Version of the encoding, increased on any incompatible change. Version 1 only
+/// has the powers of tau, the Lagrange basis and the hiding powers were added in
+/// version 2
+const VERSION: u8 = 2;
+
+const G1_COMPRESSED_SIZE: usize = 48;
+const G2_COMPRESSED_SIZE: usize = 96;
+
+/// Errors that can happen when loading a trusted setup
+#[derive(Debug)]
+pub enum SetupError {
+    /// Failed to read or write the setup
+    Io(std::io::Error),
+    /// The data does not start with the expected magic bytes
+    InvalidHeader,
+    /// The setup was encoded with an unsupported version
+    UnsupportedVersion(u8),
+    /// The setup contains no powers of tau
+    Empty,
+    /// The text setup is malformed
+    InvalidFormat,
+    /// A point is not a valid compressed point, is not on the curve or is not
+    /// in the prime order subgroup
+    InvalidPoint,
+}
+
+impl std::fmt::Display for SetupError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SetupError::Io(err) => write!(f, "i/o error: {}", err),
+            SetupError::InvalidHeader => write!(f, "invalid setup header"),
+            SetupError::UnsupportedVersion(v) => write!(f, "unsupported setup version {}", v),
+            SetupError::Empty => write!(f, "empty setup"),
+            SetupError::InvalidFormat => write!(f, "malformed setup"),
+            SetupError::InvalidPoint => write!(f, "invalid curve point"),
+        }
+    }
+}
+
+impl std::error::Error for SetupError {}
+
+impl From<std::io::Error> for SetupError {
+    fn from(err: std::io::Error) -> Self {
+        SetupError::Io(err)
+    }
+}
+
+impl Kzg {
+    /// Writes the setup, that is a header with
+    ///   magic(5) | version(1) | g1 count(u32 le) | g2 count(u32 le) | lagrange count(u32 le)
+    ///   | gamma g1 count(u32 le)
+    /// followed by the compressed powers of tau in G1 (48 bytes each), in G2 (96 bytes
+    /// each), the G1 Lagrange basis (48 bytes each) and the hiding powers in G1 (48
+    /// bytes each)
+    pub fn to_writer<W: Write>(&self, w: &mut W) -> Result<(), SetupError> {
+        w.write_all(MAGIC)?;
+        w.write_all(&[VERSION])?;
+        w.write_all(&(self.pow_tau_g1.len() as u32).to_le_bytes())?;
+        w.write_all(&(self.pow_tau_g2.len() as u32).to_le_bytes())?;
+        w.write_all(&(self.lagrange_g1.len() as u32).to_le_bytes())?;
+        w.write_all(&(self.pow_gamma_tau_g1.len() as u32).to_le_bytes())?;
+
+        write_g1(w, &self.pow_tau_g1)?;
+        for p in self.pow_tau_g2.iter() {
+            w.write_all(&p.to_compressed())?;
+        }
+        write_g1(w, &self.lagrange_g1)?;
+        write_g1(w, &self.pow_gamma_tau_g1)
+    }
+
+    /// Reads a setup written with `to_writer`, checking that all the points are on
+    /// the curve and in the right subgroup. Setups of version 1, that only have the
+    /// powers of tau, are also accepted
+    /// # Example
+    /// ```
+    /// use a0kzg::{Kzg, Poly};
+    /// let kzg = Kzg::trusted_setup(4);
+    /// let kzg2 = Kzg::from_bytes(&kzg.to_bytes()).unwrap();
+    /// let p = Poly::from(&[1, 2, 3]);
+    /// assert_eq!(kzg.commit(&p), kzg2.commit(&p));
+    /// ```
+    pub fn from_reader<R: Read>(r: &mut R) -> Result<Self, SetupError> {
+        let mut magic = [0u8; 5];
+        r.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(SetupError::InvalidHeader);
+        }
+        let mut version = [0u8; 1];
+        r.read_exact(&mut version)?;
+        if version[0] != 1 && version[0] != VERSION {
+            return Err(SetupError::UnsupportedVersion(version[0]));
+        }
+        let mut len = [0u8; 4];
+        r.read_exact(&mut len)?;
+        let g1_len = u32::from_le_bytes(len) as usize;
+        r.read_exact(&mut len)?;
+        let g2_len = u32::from_le_bytes(len) as usize;
+        // version 1 has neither the Lagrange count nor the gamma count
+        let (lagrange_len, gamma_len) = if version[0] == 1 {
+            (0, 0)
+        } else {
+            r.read_exact(&mut len)?;
+            let lagrange_len = u32::from_le_bytes(len) as usize;
+            r.read_exact(&mut len)?;
+            (lagrange_len, u32::from_le_bytes(len) as usize)
+        };
+        if g1_len == 0 || g2_len == 0 {
+            return Err(SetupError::Empty);
+        }
+
+        let pow_tau_g1 = read_g1(r, g1_len)?;
+
+        let mut buf = [0u8; G2_COMPRESSED_SIZE];
+        let pow_tau_g2 = (0..g2_len)
+            .map(|_| {
+                r.read_exact(&mut buf)?;
+                let p: Option<G2Affine> = G2Affine::from_compressed(&buf).into();
+                p.ok_or(SetupError::InvalidPoint)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let lagrange_g1 = read_g1(r, lagrange_len)?;
+        let pow_gamma_tau_g1 = read_g1(r, gamma_len)?;
+
+        Ok(Self {
+            pow_tau_g1,
+            pow_tau_g2,
+            lagrange_g1,
+            pow_gamma_tau_g1,
+        })
+    }
+
+    /// Encodes the setup into bytes, see `to_writer`
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        self.to_writer(&mut bytes)
+            .expect("writing to a vec does not fail");
+        bytes
+    }
+
+    /// Decodes the setup from bytes, see `from_reader`
+    pub fn from_bytes(mut bytes: &[u8]) -> Result<Self, SetupError> {
+        Self::from_reader(&mut bytes)
+    }
+
+    /// Writes the setup into the file at `path`
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), SetupError> {
+        let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        self.to_writer(&mut file)?;
+        file.flush()?;
+        Ok(())
+    }
+
+    /// Reads the setup from the file at `path`
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, SetupError> {
+        let mut file = std::io::BufReader::new(std::fs::File::open(path)?);
+        Self::from_reader(&mut file)
+    }
+
+    /// Reads the output of the Ethereum KZG ceremony in the c-kzg `trusted_setup.txt`
+    /// format, that is, the number of G1 and G2 points followed by the hex encoded
+    /// G1 points in Lagrange form and the G2 points in monomial form. Newer versions of
+    /// the file also append the G1 points in monomial form, if they are missing they
+    /// are computed from the Lagrange ones
+    pub fn from_trusted_setup_txt<R: BufRead>(r: R) -> Result<Self, SetupError> {
+        let mut text = String::new();
+        for line in r.lines() {
+            text.push_str(&line?);
+            text.push('\n');
+        }
+        let mut tokens = text.split_whitespace();
+        let mut next_len = || -> Result<usize, SetupError> {
+            tokens
+                .next()
+                .and_then(|t| t.parse().ok())
+                .ok_or(SetupError::InvalidFormat)
+        };
+        let (g1_len, g2_len) = (next_len()?, next_len()?);
+        if g1_len == 0 || g2_len == 0 {
+            return Err(SetupError::Empty);
+        }
+        if !g1_len.is_power_of_two() {
+            return Err(SetupError::InvalidFormat);
+        }
+
+        let mut tokens = text.split_whitespace().skip(2);
+        let lagrange_g1 = (0..g1_len)
+            .map(|_| parse_g1(tokens.next()))
+            .collect::<Result<Vec<_>, _>>()?;
+        let pow_tau_g2 = (0..g2_len)
+            .map(|_| {
+                let bytes = decode_hex(tokens.next(), G2_COMPRESSED_SIZE)?;
+                let mut buf = [0u8; G2_COMPRESSED_SIZE];
+                buf.copy_from_slice(&bytes);
+                let p: Option<G2Affine> = G2Affine::from_compressed(&buf).into();
+                p.ok_or(SetupError::InvalidPoint)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let monomial_g1 = tokens.clone().next().is_some();
+        let pow_tau_g1 = if monomial_g1 {
+            (0..g1_len)
+                .map(|_| parse_g1(tokens.next()))
+                .collect::<Result<Vec<_>, _>>()?
+        } else {
+            // the monomial form are the evaluations of the Lagrange basis
+            //   Σ_i w^(i·k)·L_i(x) = x^k
+            let mut pow_tau_g1: Vec<G1Projective> =
+                lagrange_g1.iter().map(G1Projective::from).collect();
+            EvaluationDomain::new(g1_len)
+                .ok_or(SetupError::InvalidFormat)?
+                .fft_g1(&mut pow_tau_g1);
+            let mut pow_tau_g1_affine = vec![G1Affine::identity(); g1_len];
+            G1Projective::batch_normalize(&pow_tau_g1, &mut pow_tau_g1_affine);
+            pow_tau_g1_affine
+        };
+        if tokens.next().is_some() {
+            return Err(SetupError::InvalidFormat);
+        }
+
+        Ok(Self {
+            pow_tau_g1,
+            pow_tau_g2,
+            lagrange_g1,
+            pow_gamma_tau_g1: Vec::new(),
+        })
+    }
+
+    /// Reads the Ethereum KZG ceremony `trusted_setup.txt` file at `path`, see
+    /// `from_trusted_setup_txt`
+    pub fn load_trusted_setup_txt<P: AsRef<Path>>(path: P) -> Result<Self, SetupError> {
+        let file = std::io::BufReader::new(std::fs::File::open(path)?);
+        Self::from_trusted_setup_txt(file)
+    }
+}
+
+fn write_g1<W: Write>(w: &mut W, points: &[G1Affine]) -> Result<(), SetupError> {
+    for p in points.iter() {
+        w.write_all(&p.to_compressed())?;
+    }
+    Ok(())
+}
+
+fn read_g1<R: Read>(r: &mut R, len: usize) -> Result<Vec<G1Affine>, SetupError> {
+    let mut buf = [0u8; G1_COMPRESSED_SIZE];
+    (0..len)
+        .map(|_| {
+            r.read_exact(&mut buf)?;
+            let p: Option<G1Affine> = G1Affine::from_compressed(&buf).into();
+            p.ok_or(SetupError::InvalidPoint)
+        })
+        .collect()
+}
+
+fn parse_g1(token: Option<&str>) -> Result<G1Affine, SetupError> {
+    let bytes = decode_hex(token, G1_COMPRESSED_SIZE)?;
+    let mut buf = [0u8; G1_COMPRESSED_SIZE];
+    buf.copy_from_slice(&bytes);
+    let p: Option<G1Affine> = G1Affine::from_compressed(&buf).into();
+    p.ok_or(SetupError::InvalidPoint)
+}
+
+fn decode_hex(token: Option<&str>, len: usize) -> Result<Vec<u8>, SetupError> {
+    let token = token.ok_or(SetupError::InvalidFormat)?;
+    let token = token.strip_prefix("0x").unwrap_or(token);
+    match hex::decode(token) {
+        Ok(bytes) if bytes.len() == len => Ok(bytes),
+        _ => Err(SetupError::InvalidFormat),
+    }
+}
+
+/// The Ethereum KZG ceremony setup, loaded once for all the tests
+#[cfg(test)]
+pub(crate) fn ethereum_kzg() -> &'static Kzg {
+    static KZG: once_cell::sync::Lazy<Kzg> = once_cell::sync::Lazy::new(|| {
+        Kzg::load_trusted_setup_txt(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/src/trusted_setup.txt"
+        ))
+        .unwrap()
+    });
+    &KZG
+}
+
+#[test]
+fn test_setup_roundtrip() {
+    use super::{kzg::KzgError, poly::Poly};
+
+    let kzg = Kzg::trusted_setup(6);
+    let bytes = kzg.to_bytes();
+    assert_eq!(bytes.len(), 22 + 6 * 48 + 6 * 96 + 6 * 48);
+
+    let kzg2 = Kzg::from_bytes(&bytes).unwrap();
+    assert_eq!(kzg.pow_tau_g1, kzg2.pow_tau_g1);
+    assert_eq!(kzg.pow_tau_g2, kzg2.pow_tau_g2);
+    assert_eq!(kzg.pow_gamma_tau_g1, kzg2.pow_gamma_tau_g1);
+
+    // version 1 is magic | 1 | g1 count | g2 count and the powers of tau
+    let mut v1 = bytes[..14].to_vec();
+    v1[5] = 1;
+    v1.extend_from_slice(&bytes[22..22 + 6 * 48 + 6 * 96]);
+    let kzg1 = Kzg::from_bytes(&v1).unwrap();
+    assert_eq!(kzg.pow_tau_g1, kzg1.pow_tau_g1);
+    assert_eq!(kzg.pow_tau_g2, kzg1.pow_tau_g2);
+    assert!(kzg1.lagrange_g1.is_empty());
+    assert!(kzg1.pow_gamma_tau_g1.is_empty());
+    assert_eq!(
+        kzg1.commit_hiding(&Poly::from(&[1, 2]), &Poly::from(&[3, 4])),
+        Err(KzgError::HidingNotSupported)
+    );
+
+    let path = std::env::temp_dir().join(format!("a0kzg-setup-{}.bin", std::process::id()));
+    kzg.save(&path).unwrap();
+    let kzg3 = Kzg::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(kzg.pow_tau_g1, kzg3.pow_tau_g1);
+}
+
+#[test]
+fn test_setup_invalid() {
+    let kzg = Kzg::trusted_setup(2);
+    let bytes = kzg.to_bytes();
+
+    let mut bad_magic = bytes.clone();
+    bad_magic[0] = b'b';
+    assert!(matches!(
+        Kzg::from_bytes(&bad_magic),
+        Err(SetupError::InvalidHeader)
+    ));
+
+    let mut bad_version = bytes.clone();
+    bad_version[5] = 3;
+    assert!(matches!(
+        Kzg::from_bytes(&bad_version),
+        Err(SetupError::UnsupportedVersion(3))
+    ));
+
+    assert!(matches!(
+        Kzg::from_bytes(&bytes[..bytes.len() - 1]),
+        Err(SetupError::Io(_))
+    ));
+
+    // flip a bit of the x coordinate of the second G1 point so it is no longer valid
+    let mut bad_point = bytes;
+    bad_point[22 + 48 + 47] ^= 1;
+    assert!(matches!(
+        Kzg::from_bytes(&bad_point),
+        Err(SetupError::InvalidPoint)
+    ));
+}
+
+#[test]
+fn test_trusted_setup_txt() {
+    // write a small setup in the ceremony format, without the monomial G1 points
+    let kzg = Kzg::trusted_setup(8);
+    let mut lagrange_g1: Vec<G1Projective> =
+        kzg.pow_tau_g1.iter().map(G1Projective::from).collect();
+    EvaluationDomain::new(8).unwrap().ifft_g1(&mut lagrange_g1);
+    let lagrange_g1: Vec<G1Affine> = lagrange_g1.iter().map(G1Affine::from).collect();
+    let mut txt = String::from("8\n8\n");
+    for p in lagrange_g1.iter() {
+        txt += &format!("{}\n", hex::encode(p.to_compressed()));
+    }
+    for p in kzg.pow_tau_g2.iter() {
+        txt += &format!("{}\n", hex::encode(p.to_compressed()));
+    }
+
+    let loaded = Kzg::from_trusted_setup_txt(txt.as_bytes()).unwrap();
+    assert_eq!(loaded.pow_tau_g1, kzg.pow_tau_g1);
+    assert_eq!(loaded.pow_tau_g2, kzg.pow_tau_g2);
+    assert_eq!(loaded.lagrange_g1, lagrange_g1);
+
+    assert!(matches!(
+        Kzg::from_trusted_setup_txt(&txt.as_bytes()[..txt.len() - 20]),
+        Err(SetupError::InvalidFormat)
+    ));
+    assert!(matches!(
+        Kzg::from_trusted_setup_txt(txt.replacen("8", "7", 1).as_bytes()),
+        Err(SetupError::InvalidFormat)
+    ));
+}
+
+#[test]
+fn test_ethereum_trusted_setup() {
+    use super::msm::msm_g1;
+    let kzg = ethereum_kzg();
+    assert_eq!(kzg.pow_tau_g1.len(), 4096);
+    assert_eq!(kzg.lagrange_g1.len(), 4096);
+    assert_eq!(kzg.pow_tau_g2.len(), 65);
+
+    // the lagrange basis adds up to 1 and both forms agree on tau
+    let g1 = G1Affine::generator();
+    assert_eq!(kzg.pow_tau_g1[0], g1);
+    let ones = vec![bls12_381::Scalar::one(); 4096];
+    assert_eq!(G1Affine::from(msm_g1(&kzg.lagrange_g1, &ones)), g1);
+    let domain = EvaluationDomain::new(4096).unwrap();
+    let tau_g1 = msm_g1(&kzg.lagrange_g1, &domain.elements());
+    assert_eq!(G1Affine::from(tau_g1), kzg.pow_tau_g1[1]);
+    assert_eq!(kzg.pow_tau_g2[0], G2Affine::generator());
+}
@@ -0,0 +1,261 @@
+//! This module implements the multi-polinomial multi-point opening of Boneh, Drake,
+//! Fisch and Gabizon (SHPLONK), each polinomial is opened at its own set of points
+//! and the proof is always two G1 points
+//! see https://eprint.iacr.org/2020/081.pdf
+//!
+//! Being S_i the points of f_i, T the union of all S_i, r_i the polinomial that
+//! interpolates f_i in S_i and Z_S the polinomial that vanishes in S:
+//! - the prover sends W = [h(tau)]₁, h = Σ γ^i·(f_i - r_i)/Z_S_i
+//! - the prover sends W' = [L(tau)/(tau - z)]₁, where
+//!   L = Σ γ^i·Z_T\S_i(z)·(f_i - r_i(z)) - Z_T(z)·h, that vanishes at z
+//! - the verifier computes F = Σ γ^i·Z_T\S_i(z)·(C_i - [r_i(z)]₁) - Z_T(z)·W, the
+//!   commitment to L, and checks e(F + z·W', [1]₂) = e(W', [tau]₂)
+
+use super::{
+    domain::powers,
+    kzg::{Commitment, Kzg, KzgError, Proof},
+    msm::msm_g1,
+    poly::Poly,
+    transcript::{Transcript, TranscriptBackend},
+};
+use bls12_381::{multi_miller_loop, G1Affine, G1Projective, G2Affine, G2Prepared, Gt, Scalar};
+
+/// Proof for many polinomials opened at many points
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MultiProof {
+    /// Commitment to h = Σ γ^i·(f_i - r_i)/Z_S_i
+    pub w: Proof,
+    /// Opening proof of L at z
+    pub w_prime: Proof,
+}
+
+impl Kzg {
+    /// Opens each polinomial at its own set of (x, y) `points`. The commitments to the
+    /// polinomials should already be in the `transcript`, the points are absorbed here
+    /// # Example
+    /// ```
+    /// use a0kzg::{Kzg, Poly, Scalar, Sha256Transcript};
+    /// let kzg = Kzg::trusted_setup(8);
+    /// let (p0, p1) = (Poly::from(&[1, 2, 3]), Poly::from(&[4, 5, 6, 7]));
+    /// let (c0, c1) = (kzg.commit(&p0).unwrap(), kzg.commit(&p1).unwrap());
+    /// let point = |p: &Poly, x: u64| (Scalar::from(x), p.eval(&Scalar::from(x)));
+    /// let points0 = vec![point(&p0, 1), point(&p0, 2)];
+    /// let points1 = vec![point(&p1, 2), point(&p1, 3), point(&p1, 4)];
+    ///
+    /// let mut transcript = Sha256Transcript::new(b"example");
+    /// transcript.append_commitment(b"c", &c0);
+    /// transcript.append_commitment(b"c", &c1);
+    /// let proof = kzg
+    ///     .open_multi(&[(&p0, &points0), (&p1, &points1)], &mut transcript)
+    ///     .unwrap();
+    ///
+    /// let mut transcript = Sha256Transcript::new(b"example");
+    /// transcript.append_commitment(b"c", &c0);
+    /// transcript.append_commitment(b"c", &c1);
+    /// assert!(kzg.verify_multi(&[(c0, &points0), (c1, &points1)], &proof, &mut transcript));
+    /// ```
+    pub fn open_multi<B: TranscriptBackend>(
+        &self,
+        queries: &[(&Poly, &[(Scalar, Scalar)])],
+        transcript: &mut Transcript<B>,
+    ) -> Result<MultiProof, KzgError> {
+        absorb_points(transcript, queries.iter().map(|(_, points)| *points));
+        let gamma = transcript.challenge_scalar(b"gamma");
+
+        // h = Σ γ^i·(f_i - r_i)/Z_S_i
+        let mut h = Poly::zero();
+        let mut rs = Vec::with_capacity(queries.len());
+        for ((f, points), gamma_pow) in queries.iter().zip(powers(gamma, queries.len())) {
+            let r = Poly::try_interpolate_fast(points).map_err(|_| KzgError::DuplicateX)?;
+            let mut num = (*f).clone();
+            num -= &r;
+            let (q, rem) = num / Kzg::z_poly_of(points);
+            if !rem.is_zero() {
+                return Err(KzgError::PointNotOnPolynomial);
+            }
+            h += &(&q * &gamma_pow);
+            rs.push(r);
+        }
+        let w = self.commit(&h)?;
+        transcript.append_proof(b"w", &w);
+        let z = transcript.challenge_scalar(b"z");
+
+        // L = Σ γ^i·Z_T\S_i(z)·(f_i - r_i(z)) - Z_T(z)·h
+        let (z_t, z_t_minus_s) = vanishing_evals(queries.iter().map(|(_, points)| *points), &z);
+        let mut l = &h * &-z_t;
+        for (((f, _), r), (gamma_pow, z_i)) in queries
+            .iter()
+            .zip(rs.iter())
+            .zip(powers(gamma, queries.len()).iter().zip(z_t_minus_s.iter()))
+        {
+            let mut term = (*f).clone();
+            term -= &Poly::new(vec![r.eval(&z)]);
+            l += &(&term * &(gamma_pow * z_i));
+        }
+        let (q, _) = l.div_by_linear(&z);
+        let w_prime = self.commit(&q)?;
+        transcript.append_proof(b"w_prime", &w_prime);
+
+        Ok(MultiProof { w, w_prime })
+    }
+
+    /// Verifies a proof generated with `open_multi` for the `commitment`s and `points`
+    /// of each polinomial, the transcript should be in the same state than the one
+    /// used by the prover
+    pub fn verify_multi<B: TranscriptBackend>(
+        &self,
+        queries: &[(Commitment, &[(Scalar, Scalar)])],
+        proof: &MultiProof,
+        transcript: &mut Transcript<B>,
+    ) -> bool {
+        let tau_g2 = match self.pow_tau_g2.get(1) {
+            Some(tau_g2) => tau_g2,
+            None => return false,
+        };
+
+        absorb_points(transcript, queries.iter().map(|(_, points)| *points));
+        let gamma = transcript.challenge_scalar(b"gamma");
+        transcript.append_proof(b"w", &proof.w);
+        let z = transcript.challenge_scalar(b"z");
+        transcript.append_proof(b"w_prime", &proof.w_prime);
+
+        // F = Σ γ^i·Z_T\S_i(z)·C_i - [Σ γ^i·Z_T\S_i(z)·r_i(z)]₁ - Z_T(z)·W
+        let (z_t, z_t_minus_s) = vanishing_evals(queries.iter().map(|(_, points)| *points), &z);
+        let mut bases = Vec::with_capacity(queries.len() + 1);
+        let mut scalars = Vec::with_capacity(queries.len() + 1);
+        let mut r_lincomb = Scalar::zero();
+        for (((c, points), gamma_pow), z_i) in queries
+            .iter()
+            .zip(powers(gamma, queries.len()))
+            .zip(z_t_minus_s.iter())
+        {
+            let r = match Poly::try_interpolate_fast(points) {
+                Ok(r) => r,
+                Err(_) => return false,
+            };
+            bases.push(*c);
+            scalars.push(gamma_pow * z_i);
+            r_lincomb += gamma_pow * z_i * r.eval(&z);
+        }
+        bases.push(proof.w);
+        scalars.push(-z_t);
+        // z·W' is added here, so F + z·W' is a single MSM
+        bases.push(proof.w_prime);
+        scalars.push(z);
+
+        let mut bases_affine = vec![G1Affine::identity(); bases.len()];
+        G1Projective::batch_normalize(&bases, &mut bases_affine);
+        let lhs = msm_g1(&bases_affine, &scalars) - G1Affine::generator() * r_lincomb;
+
+        let result = multi_miller_loop(&[
+            (&lhs.into(), &G2Prepared::from(G2Affine::generator())),
+            (&proof.w_prime.into(), &G2Prepared::from(-tau_g2)),
+        ])
+        .final_exponentiation();
+        result == Gt::identity()
+    }
+}
+
+/// Absorbs all the (x, y) of all the queries
+fn absorb_points<'a, B: TranscriptBackend>(
+    transcript: &mut Transcript<B>,
+    queries: impl Iterator<Item = &'a [(Scalar, Scalar)]>,
+) {
+    for points in queries {
+        transcript.append_message(b"points", &(points.len() as u64).to_le_bytes());
+        for (x, y) in points.iter() {
+            transcript.append_scalar(b"x", x);
+            transcript.append_scalar(b"y", y);
+        }
+    }
+}
+
+/// Returns Z_T(z) and Z_T\S_i(z) for each set of points S_i, being T the union of
+/// all the x of all the sets
+fn vanishing_evals<'a>(
+    queries: impl Iterator<Item = &'a [(Scalar, Scalar)]> + Clone,
+    z: &Scalar,
+) -> (Scalar, Vec<Scalar>) {
+    let mut t: Vec<Scalar> = Vec::new();
+    for (x, _) in queries.clone().flatten() {
+        if !t.contains(x) {
+            t.push(*x);
+        }
+    }
+    let z_t = t.iter().fold(Scalar::one(), |acc, x| acc * (z - x));
+    let z_t_minus_s = queries
+        .map(|points| {
+            t.iter()
+                .filter(|x| !points.iter().any(|(s, _)| s == *x))
+                .fold(Scalar::one(), |acc, x| acc * (z - x))
+        })
+        .collect();
+    (z_t, z_t_minus_s)
+}
+
+#[test]
+fn test_shplonk() {
+    use super::transcript::Sha256Transcript;
+
+    let kzg = Kzg::trusted_setup(16);
+    let polys: Vec<Poly> = (0..4u64)
+        .map(|i| Poly::new((0..16).map(|j| Scalar::from(i * 16 + j + 5)).collect()))
+        .collect();
+    let commitments: Vec<Commitment> = polys.iter().map(|p| kzg.commit(p).unwrap()).collect();
+    // overlapping sets of points, including a rotation like query
+    let xs: Vec<Vec<u64>> = vec![vec![1], vec![1, 2], vec![2, 3, 5, 7], vec![9]];
+    let points: Vec<Vec<(Scalar, Scalar)>> = polys
+        .iter()
+        .zip(xs.iter())
+        .map(|(p, xs)| {
+            xs.iter()
+                .map(|x| (Scalar::from(*x), p.eval(&Scalar::from(*x))))
+                .collect()
+        })
+        .collect();
+
+    let prove = |points: &[Vec<(Scalar, Scalar)>]| {
+        let queries: Vec<(&Poly, &[(Scalar, Scalar)])> = polys
+            .iter()
+            .zip(points.iter())
+            .map(|(p, s)| (p, s.as_slice()))
+            .collect();
+        kzg.open_multi(&queries, &mut Sha256Transcript::new(b"test"))
+    };
+    let verify = |points: &[Vec<(Scalar, Scalar)>], proof: &MultiProof| {
+        let queries: Vec<(Commitment, &[(Scalar, Scalar)])> = commitments
+            .iter()
+            .zip(points.iter())
+            .map(|(c, s)| (*c, s.as_slice()))
+            .collect();
+        kzg.verify_multi(&queries, proof, &mut Sha256Transcript::new(b"test"))
+    };
+
+    let proof = prove(&points).unwrap();
+    assert!(verify(&points, &proof));
+
+    // wrong evaluations are rejected by the prover and the verifier
+    let mut bad = points.clone();
+    bad[2][1].1 += Scalar::one();
+    assert_eq!(prove(&bad), Err(KzgError::PointNotOnPolynomial));
+    assert!(!verify(&bad, &proof));
+
+    // the proof is bound to the transcript and to all the points
+    let queries: Vec<(Commitment, &[(Scalar, Scalar)])> = commitments
+        .iter()
+        .zip(points.iter())
+        .map(|(c, s)| (*c, s.as_slice()))
+        .collect();
+    assert!(!kzg.verify_multi(&queries, &proof, &mut Sha256Transcript::new(b"other")));
+    assert!(!verify(&points[..3], &proof));
+    let swapped = MultiProof {
+        w: proof.w_prime,
+        w_prime: proof.w,
+    };
+    assert!(!verify(&points, &swapped));
+
+    let mut dup = points.clone();
+    dup[1][1] = dup[1][0];
+    assert_eq!(prove(&dup), Err(KzgError::DuplicateX));
+    assert!(!verify(&dup, &proof));
+}
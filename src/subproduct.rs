@@ -0,0 +1,190 @@
+//! This module provides subproduct trees, used for evaluating a polinomial in many
+//! points and for interpolating many points in O(n log² n)
+//! see https://cr.yp.to/arith/scaledmod-20040820.pdf
+
+use super::poly::{Poly, PolyError};
+use bls12_381::Scalar;
+
+/// Binary tree whose leaves are (x - x_i) and each node is the product of its
+/// children, so the root is the polinomial that vanishes in all the x_i
+pub(crate) struct SubproductTree {
+    // levels[0] are the leaves, last level is the root
+    levels: Vec<Vec<Poly>>,
+}
+
+impl SubproductTree {
+    /// Builds the tree for the `xs` points, `xs` should not be empty
+    pub fn new(xs: &[Scalar]) -> Self {
+        let leaves: Vec<Poly> = xs
+            .iter()
+            .map(|x| Poly::new(vec![-x, Scalar::one()]))
+            .collect();
+        let mut levels = vec![leaves];
+        while levels[levels.len() - 1].len() > 1 {
+            let next = levels[levels.len() - 1]
+                .chunks(2)
+                .map(|pair| match pair {
+                    [l, r] => l * r,
+                    [l] => l.clone(),
+                    _ => unreachable!(),
+                })
+                .collect();
+            levels.push(next);
+        }
+        Self { levels }
+    }
+
+    /// Returns the product of all (x - x_i)
+    pub fn root(&self) -> &Poly {
+        &self.levels[self.levels.len() - 1][0]
+    }
+
+    /// Evaluates `poly` at all the x_i, by reducing it modulo each node going from
+    /// the root to the leaves
+    pub fn eval(&self, poly: &Poly) -> Vec<Scalar> {
+        let mut rems = vec![(poly.clone() / self.root().clone()).1];
+        for level in self.levels.iter().rev().skip(1) {
+            rems = level
+                .iter()
+                .enumerate()
+                .map(|(i, m)| (rems[i / 2].clone() / m.clone()).1)
+                .collect();
+        }
+        rems.iter().map(|r| r.0[0]).collect()
+    }
+
+    /// Computes Σ c_i·Π_{j≠i}(x - x_j) by combining the children of each node from
+    /// the leaves to the root, left·M_right + right·M_left
+    pub fn linear_combination(&self, c: &[Scalar]) -> Poly {
+        let mut acc: Vec<Poly> = c.iter().map(|c| Poly::new(vec![*c])).collect();
+        for level in self.levels.iter().take(self.levels.len() - 1) {
+            acc = acc
+                .chunks(2)
+                .zip(level.chunks(2))
+                .map(|(a, m)| match (a, m) {
+                    ([a_l, a_r], [m_l, m_r]) => {
+                        let mut p = a_l * m_r;
+                        p += &(a_r * m_l);
+                        p
+                    }
+                    ([a_l], _) => a_l.clone(),
+                    _ => unreachable!(),
+                })
+                .collect();
+        }
+        acc.swap_remove(0)
+    }
+}
+
+impl Poly {
+    /// Returns the formal derivative of the polinomial
+    /// # Examples
+    /// ```
+    ///    use a0kzg::Poly;
+    ///    assert_eq!(Poly::from(&[1, 2, 3]).derivative(), Poly::from(&[2, 6]));
+    /// ```
+    pub fn derivative(&self) -> Poly {
+        if self.0.len() == 1 {
+            return Poly::zero();
+        }
+        Poly::new(
+            self.0
+                .iter()
+                .enumerate()
+                .skip(1)
+                .map(|(i, c)| c * Scalar::from(i as u64))
+                .collect(),
+        )
+    }
+
+    /// Evals the polinomial at all the `xs` points using a subproduct tree
+    /// # Examples
+    /// ```
+    ///    use a0kzg::{Poly, Scalar};
+    ///    let p = Poly::from(&[1, 2, 1]);
+    ///    assert_eq!(
+    ///      p.eval_many(&[Scalar::from(1), Scalar::from(2)]),
+    ///      vec![Scalar::from(4), Scalar::from(9)]
+    ///    );
+    /// ```
+    pub fn eval_many(&self, xs: &[Scalar]) -> Vec<Scalar> {
+        if xs.is_empty() {
+            return Vec::new();
+        }
+        SubproductTree::new(xs).eval(self)
+    }
+
+    /// Creates a polinomial that contains a set of `p` points like `lagrange`, but
+    /// using a subproduct tree. Being M=Π(x - x_i), the result is
+    /// Σ y_i/M'(x_i)·M(x)/(x - x_i)
+    /// # Examples
+    /// ```
+    ///    use a0kzg::{Poly, Scalar};
+    ///    assert_eq!(
+    ///      Poly::interpolate_fast(&vec![
+    ///          (Scalar::from(1), Scalar::from(1)),
+    ///          (Scalar::from(2), Scalar::from(2))
+    ///      ]),
+    ///      Poly::from(&[0, 1])
+    ///    );
+    /// ```
+    pub fn interpolate_fast(p: &[(Scalar, Scalar)]) -> Self {
+        Self::try_interpolate_fast(p).expect("points with duplicated x")
+    }
+
+    /// Like `interpolate_fast`, but fails with `PolyError::DuplicateX` instead of
+    /// panicking if two points have the same x
+    pub fn try_interpolate_fast(p: &[(Scalar, Scalar)]) -> Result<Self, PolyError> {
+        if p.is_empty() {
+            return Ok(Poly::zero());
+        }
+        let xs: Vec<Scalar> = p.iter().map(|(x, _)| *x).collect();
+        let tree = SubproductTree::new(&xs);
+        // M'(x_i) is zero only if x_i is a multiple root of M
+        let weights = tree
+            .eval(&tree.root().derivative())
+            .iter()
+            .zip(p.iter())
+            .map(|(d, (_, y))| {
+                let d_inv: Option<Scalar> = d.invert().into();
+                d_inv.map(|d_inv| y * d_inv).ok_or(PolyError::DuplicateX)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(tree.linear_combination(&weights))
+    }
+}
+
+#[test]
+fn test_eval_many() {
+    let p = Poly::from(&[7, 3, 0, 9, 1, 4, 4, 2, 8, 8, 5, 1]);
+    let xs: Vec<Scalar> = (0..37u64).map(|x| Scalar::from(x * x + 11)).collect();
+    let expected: Vec<Scalar> = xs.iter().map(|x| p.eval(x)).collect();
+    assert_eq!(p.eval_many(&xs), expected);
+    assert_eq!(p.eval_many(&xs[..1]), expected[..1].to_vec());
+    assert_eq!(p.eval_many(&[]), vec![]);
+}
+
+#[test]
+fn test_interpolate_fast() {
+    let points: Vec<(Scalar, Scalar)> = (0..300u64)
+        .map(|i| (Scalar::from(3 * i + 1), Scalar::from(i * i * 17 + 5)))
+        .collect();
+    let l = Poly::interpolate_fast(&points);
+    assert!(l.degree() < points.len());
+    assert_eq!(
+        l.eval_many(&points.iter().map(|p| p.0).collect::<Vec<_>>()),
+        points.iter().map(|p| p.1).collect::<Vec<_>>()
+    );
+    assert_eq!(
+        Poly::interpolate_fast(&points[..5]),
+        Poly::lagrange(&points[..5])
+    );
+    assert_eq!(
+        Poly::interpolate_fast(&points[..1]),
+        Poly::lagrange(&points[..1])
+    );
+
+    let mut dup = points[..10].to_vec();
+    dup.push(points[3]);
+    assert_eq!(Poly::try_interpolate_fast(&dup), Err(PolyError::DuplicateX));
+}
@@ -0,0 +1,196 @@
+//! This module provides a Fiat-Shamir transcript, used to derive the challenges of
+//! the protocols from all the messages exchanged so far, so they can be made
+//! non-interactive
+//! see https://merlin.cool/
+
+use super::{
+    bandersnatch::{BandersnatchPoint, BandersnatchScalar},
+    kzg::{Commitment, Proof},
+};
+use bls12_381::{G1Affine, Scalar};
+use ff::PrimeField;
+use sha2::{Digest, Sha256};
+
+/// The hash that a `Transcript` uses to absorb messages and squeeze challenges
+pub trait TranscriptBackend {
+    /// Starts a new state separated with the `domain` label
+    fn new(domain: &'static [u8]) -> Self;
+    /// Absorbs `message` under `label`
+    fn append(&mut self, label: &'static [u8], message: &[u8]);
+    /// Fills `dest` with bytes that depend on everything absorbed and `label`, the
+    /// state is updated so next challenges are different
+    fn challenge_bytes(&mut self, label: &'static [u8], dest: &mut [u8]);
+}
+
+/// SHA-256 backend, every message is absorbed as len(label)|label|len(msg)|msg and
+/// challenges are SHA-256(state|label|counter) blocks, then fed back into the state
+#[derive(Clone)]
+pub struct Sha256Backend {
+    state: Sha256,
+}
+
+impl Sha256Backend {
+    fn absorb(&mut self, label: &[u8], message: &[u8]) {
+        self.state.update((label.len() as u64).to_le_bytes());
+        self.state.update(label);
+        self.state.update((message.len() as u64).to_le_bytes());
+        self.state.update(message);
+    }
+}
+
+impl TranscriptBackend for Sha256Backend {
+    fn new(domain: &'static [u8]) -> Self {
+        let mut backend = Self {
+            state: Sha256::new(),
+        };
+        backend.absorb(b"dom-sep", domain);
+        backend
+    }
+
+    fn append(&mut self, label: &'static [u8], message: &[u8]) {
+        self.absorb(label, message);
+    }
+
+    fn challenge_bytes(&mut self, label: &'static [u8], dest: &mut [u8]) {
+        self.absorb(label, &(dest.len() as u64).to_le_bytes());
+        for (i, chunk) in dest.chunks_mut(32).enumerate() {
+            let mut h = self.state.clone();
+            h.update((i as u64).to_le_bytes());
+            chunk.copy_from_slice(&h.finalize()[..chunk.len()]);
+        }
+        self.absorb(b"challenge", dest);
+    }
+}
+
+/// Merlin backend, a STROBE transcript over the Keccak-f[1600] permutation
+#[derive(Clone)]
+pub struct MerlinBackend(merlin::Transcript);
+
+impl TranscriptBackend for MerlinBackend {
+    fn new(domain: &'static [u8]) -> Self {
+        Self(merlin::Transcript::new(domain))
+    }
+
+    fn append(&mut self, label: &'static [u8], message: &[u8]) {
+        self.0.append_message(label, message);
+    }
+
+    fn challenge_bytes(&mut self, label: &'static [u8], dest: &mut [u8]) {
+        self.0.challenge_bytes(label, dest);
+    }
+}
+
+/// A Fiat-Shamir transcript, prover and verifier must absorb the same messages in
+/// the same order to squeeze the same challenges
+#[derive(Clone)]
+pub struct Transcript<B: TranscriptBackend = Sha256Backend> {
+    backend: B,
+}
+
+/// A transcript over SHA-256
+pub type Sha256Transcript = Transcript<Sha256Backend>;
+/// A transcript over Merlin
+pub type MerlinTranscript = Transcript<MerlinBackend>;
+
+impl<B: TranscriptBackend> Transcript<B> {
+    /// Creates a transcript, `domain` separates the protocols so challenges from one
+    /// cannot be replayed into another
+    /// # Examples
+    /// ```
+    ///    use a0kzg::{Scalar, Sha256Transcript};
+    ///    let mut prover = Sha256Transcript::new(b"example");
+    ///    let mut verifier = Sha256Transcript::new(b"example");
+    ///    prover.append_scalar(b"y", &Scalar::from(3));
+    ///    verifier.append_scalar(b"y", &Scalar::from(3));
+    ///    assert_eq!(prover.challenge_scalar(b"r"), verifier.challenge_scalar(b"r"));
+    /// ```
+    pub fn new(domain: &'static [u8]) -> Self {
+        Self {
+            backend: B::new(domain),
+        }
+    }
+
+    /// Absorbs raw bytes
+    pub fn append_message(&mut self, label: &'static [u8], message: &[u8]) {
+        self.backend.append(label, message);
+    }
+
+    /// Absorbs a field element, little-endian encoded
+    pub fn append_scalar(&mut self, label: &'static [u8], s: &Scalar) {
+        self.backend.append(label, &s.to_bytes());
+    }
+
+    /// Absorbs a commitment, compressed encoded
+    pub fn append_commitment(&mut self, label: &'static [u8], c: &Commitment) {
+        self.backend
+            .append(label, &G1Affine::from(c).to_compressed());
+    }
+
+    /// Absorbs a proof, compressed encoded
+    pub fn append_proof(&mut self, label: &'static [u8], p: &Proof) {
+        self.backend
+            .append(label, &G1Affine::from(p).to_compressed());
+    }
+
+    /// Squeezes a field element, reducing 64 bytes of challenge so it is
+    /// statistically close to uniform
+    pub fn challenge_scalar(&mut self, label: &'static [u8]) -> Scalar {
+        let mut bytes = [0u8; 64];
+        self.backend.challenge_bytes(label, &mut bytes);
+        Scalar::from_bytes_wide(&bytes)
+    }
+
+    /// Absorbs a Bandersnatch point, encoded with `BandersnatchPoint::to_bytes`
+    pub fn append_bandersnatch_point(&mut self, label: &'static [u8], p: &BandersnatchPoint) {
+        self.backend.append(label, &p.to_bytes());
+    }
+
+    /// Absorbs a Bandersnatch scalar, little-endian encoded
+    pub fn append_bandersnatch_scalar(&mut self, label: &'static [u8], s: &BandersnatchScalar) {
+        self.backend.append(label, &s.to_repr().0);
+    }
+
+    /// Squeezes a Bandersnatch scalar, reducing 64 bytes of challenge
+    pub fn challenge_bandersnatch_scalar(&mut self, label: &'static [u8]) -> BandersnatchScalar {
+        let mut bytes = [0u8; 64];
+        self.backend.challenge_bytes(label, &mut bytes);
+        BandersnatchScalar::from_le_bytes_mod_order(&bytes)
+    }
+}
+
+#[cfg(test)]
+fn transcript_behaviour<B: TranscriptBackend>() {
+    let g = bls12_381::G1Projective::generator();
+    let new = |domain| {
+        let mut t = Transcript::<B>::new(domain);
+        t.append_scalar(b"z", &Scalar::from(7));
+        t.append_commitment(b"c", &(g * Scalar::from(5)));
+        t.append_proof(b"pi", &g);
+        t
+    };
+
+    let (mut t1, mut t2) = (new(b"test"), new(b"test"));
+    let c1 = t1.challenge_scalar(b"r");
+    assert_eq!(c1, t2.challenge_scalar(b"r"));
+    // squeezing again gives a different challenge
+    assert_ne!(c1, t1.challenge_scalar(b"r"));
+    // the domain separates the transcripts
+    assert_ne!(c1, new(b"other").challenge_scalar(b"r"));
+    // labels are part of the transcript
+    assert_ne!(c1, new(b"test").challenge_scalar(b"s"));
+    // and so are all the messages
+    let mut t3 = new(b"test");
+    t3.append_message(b"m", b"");
+    assert_ne!(c1, t3.challenge_scalar(b"r"));
+}
+
+#[test]
+fn test_transcript() {
+    transcript_behaviour::<Sha256Backend>();
+    transcript_behaviour::<MerlinBackend>();
+
+    // both backends are independent hashes
+    let mut sha = Sha256Transcript::new(b"test");
+    let mut merlin = MerlinTranscript::new(b"test");
+    assert_ne!(sha.challenge_scalar(b"r"), merlin.challenge_scalar(b"r"));
+}
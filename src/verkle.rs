@@ -1,64 +1,805 @@
-/*
-https://notes.ethereum.org/@vbuterin/verkle_tree_eip
-
-# Bandersnatch curve order
-BANDERSNATCH_MODULUS = \
-13108968793781547619861935127046491459309155893440570251786403306729687672801
-# Bandersnatch Pedersen basis of length 256
-PEDERSEN_BASIS = [....]
-VERKLE_NODE_WIDTH = len(PEDERSEN_BASIS)
-
-def group_to_field(point: G1Point) -> int:
-    # Not collision resistant -- not usable for Fiat-Shamir
-    assert isinstance(point, G1Point)
-    if point == bls.Z1:
-        return 0
-    else:
-        return int.from_bytes(serialize(point), 'little') % BANDERSNATCH_MODULUS
-    
-def compute_commitment_root(children: Sequence[int]) -> int:
-    o = bls.Z1
-    for generator, child in zip(PEDERSEN_BASIS, children):
-        o = bls.add(o, bls.mul(generator, child))
-    return group_to_field(o)
-
-def pedersen_leaf(stem: bytes31, value: bytes32) -> int:
-    value_lower = value[:16]
-    value_upper = value[16:]
-    return compute_commitment_root([1, # Leaf marker
-                                    int.from_bytes(stem, "little"),
-                                    int.from_bytes(value_lower, "little"),
-                                    int.from_bytes(value_upper, "little")] +
-                                    [0] * 252)
-
-def compute_main_tree_root(data: Dict[bytes32, int],
-                           prefix: bytes) -> int:
-    # Empty tree: 0
-    if len(data) == 0:
-        return 0
-    # Single element: byte-pack [key, value]
-    elif len(data) == 1:
-        key, value = list(data.items())[0]
-        return value
-    else:
-        sub_commitments = [
-            compute_main_tree_root({
-                    key: value for key, value in data.items() if
-                    key[:len(prefix) + 1] == prefix + bytes([i])
-                }, prefix + bytes([i]))
-            for i in range(VERKLE_NODE_WIDTH)
-        ]
-        return compute_commitment_root(sub_commitments)
-        
-def compute_verkle_root(data: Dict[bytes32, bytes32]) -> int:
-    # Special-case: collapse the bottom layer into commitments
-    stems = set(key[:-1] for key in data.keys())
-    data_as_stems = {}
-    for stem in stems:
-        commitment_data = [0] * 256
-        for i in range(VERKLE_NODE_WIDTH):
-            if stem + bytes([i]) in data:
-                commitment_data[i] = pedersen_leaf(stem, data[stem + bytes([i])])
-        data_as_stems[stem] = compute_commitment_root(commitment_data)
-    return compute_main_tree_root(data_as_stems, b'')
-*/
+//! This module implements a Verkle tree, a 256-ary trie where each node commits to
+//! its children with a KZG commitment over a 256 points Lagrange basis, so the
+//! commitment is a polinomial whose evaluation at w^i is the i-th child
+//! see https://notes.ethereum.org/@vbuterin/verkle_tree_eip
+//!
+//! Keys are 32 bytes, the first 31 are the stem and the last one the suffix. All the
+//! values with the same stem live in the same leaf (extension) node, that commits to
+//! [1, stem, C1, C2] where C1 commits to the values with suffix 0..128 and C2 to the
+//! ones with 128..256, each value as two evaluations (low 16 bytes + 2^128 as leaf
+//! marker, high 16 bytes). Internal nodes are created only where two stems share a
+//! prefix, so the tree shape, and its root, only depend on the stored keys.
+//!
+//! The nodes can also be committed with Pedersen commitments over Bandersnatch, see
+//! `VerkleTree::new_ipa`, that gives the commitments and roots specified for
+//! Ethereum, where commitments are mapped to fields with
+//! `BandersnatchPoint::map_to_field`. Proofs of the tree are only implemented for
+//! KZG, see `VerkleTree::prove`
+//!
+//! The nodes are kept in a `VerkleStore` by their path, in memory by default, see
+//! `VerkleTree::with_store`. Each commit of the tree stores the commitments of the
+//! updated nodes with them, and until then the computed ones are cached in memory,
+//! so an update only computes again the ones along its path.
+
+use super::{
+    bandersnatch::{msm_bandersnatch, BandersnatchPoint, BandersnatchScalar},
+    domain::EvaluationDomain,
+    ipa::Ipa,
+    kzg::{Commitment, Kzg, KzgError},
+    msm::msm_g1,
+    verkle_store::{MemoryStore, VerkleNode, VerkleStore},
+};
+use bls12_381::{G1Affine, G1Projective, Scalar};
+use sha2::{Digest, Sha256};
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, BTreeSet},
+    convert::TryInto,
+    io,
+};
+
+/// Number of children of each node
+pub const VERKLE_NODE_WIDTH: usize = 256;
+
+/// The first 31 bytes of a key, shared by all the values of a leaf node
+pub type Stem = [u8; 31];
+/// A key of the tree, stem and suffix
+pub type VerkleKey = [u8; 32];
+/// A value of the tree
+pub type VerkleValue = [u8; 32];
+
+/// The vector commitment that the nodes of a `VerkleTree` are committed with
+pub trait VerkleCommitter {
+    /// The field of the committed vectors
+    type Scalar: Copy;
+    /// The commitment of a node
+    type Commitment: Copy;
+    /// Interprets up to 31 little-endian bytes as a field element
+    fn scalar_from_le_bytes(bytes: &[u8]) -> Self::Scalar;
+    /// Commits to the 256 values of a node
+    fn commit_evals(&self, evals: &[Self::Scalar]) -> Self::Commitment;
+    /// Maps a commitment to a field element to be used as a child of its parent, the
+    /// commitment of an empty node maps to zero
+    fn commitment_to_field(c: &Self::Commitment) -> Self::Scalar;
+    /// Encodes a commitment to be stored with its node
+    fn commitment_to_bytes(c: &Self::Commitment) -> Vec<u8>;
+    /// Decodes a commitment encoded with `commitment_to_bytes`
+    fn commitment_from_bytes(bytes: &[u8]) -> Option<Self::Commitment>;
+}
+
+/// KZG commitments of the polinomials whose evaluations at w^i are the values, only
+/// valid for setups with the Lagrange basis of 256 points, see `VerkleTree::new`
+impl VerkleCommitter for Kzg {
+    type Scalar = Scalar;
+    type Commitment = Commitment;
+
+    fn scalar_from_le_bytes(bytes: &[u8]) -> Scalar {
+        let mut le = [0u8; 32];
+        le[..bytes.len()].copy_from_slice(bytes);
+        Scalar::from_bytes(&le).unwrap()
+    }
+
+    fn commit_evals(&self, evals: &[Scalar]) -> Commitment {
+        msm_g1(&self.lagrange_g1, evals)
+    }
+
+    fn commitment_to_field(c: &Commitment) -> Scalar {
+        commitment_to_field(c)
+    }
+
+    fn commitment_to_bytes(c: &Commitment) -> Vec<u8> {
+        G1Affine::from(c).to_compressed().to_vec()
+    }
+
+    fn commitment_from_bytes(bytes: &[u8]) -> Option<Commitment> {
+        let p: Option<G1Affine> = G1Affine::from_compressed(bytes.try_into().ok()?).into();
+        p.map(Commitment::from)
+    }
+}
+
+/// Pedersen commitments of the values, as specified for Ethereum
+impl VerkleCommitter for Ipa {
+    type Scalar = BandersnatchScalar;
+    type Commitment = BandersnatchPoint;
+
+    fn scalar_from_le_bytes(bytes: &[u8]) -> BandersnatchScalar {
+        BandersnatchScalar::from_le_bytes_mod_order(bytes)
+    }
+
+    fn commit_evals(&self, evals: &[BandersnatchScalar]) -> BandersnatchPoint {
+        msm_bandersnatch(&self.basis, evals)
+    }
+
+    fn commitment_to_field(c: &BandersnatchPoint) -> BandersnatchScalar {
+        c.map_to_field()
+    }
+
+    fn commitment_to_bytes(c: &BandersnatchPoint) -> Vec<u8> {
+        c.to_bytes().to_vec()
+    }
+
+    fn commitment_from_bytes(bytes: &[u8]) -> Option<BandersnatchPoint> {
+        BandersnatchPoint::from_bytes(bytes.try_into().ok()?)
+    }
+}
+
+/// A Verkle tree that stores 32 byte values, with the nodes committed with KZG and
+/// kept in memory by default
+pub struct VerkleTree<C: VerkleCommitter = Kzg, S: VerkleStore = MemoryStore> {
+    /// The commitment scheme for vectors of 256 values
+    pub(crate) committer: C,
+    pub(crate) store: S,
+    /// Paths of the nodes updated since the last commit, whose stored commitments are
+    /// not valid
+    updated: BTreeSet<Vec<u8>>,
+    /// Commitments of the nodes computed since the last commit by path, an update
+    /// removes the ones along its path
+    commitments: RefCell<BTreeMap<Vec<u8>, C::Commitment>>,
+    /// C1 (0) and C2 (1) commitments of the leaves by stem, computed since the last
+    /// commit
+    suffix_commitments: RefCell<BTreeMap<(Stem, u8), C::Commitment>>,
+}
+
+impl VerkleTree {
+    /// Creates an empty tree, the commitments use the powers of tau of `kzg`, that
+    /// must have at least 256 of them
+    /// # Example
+    /// ```
+    /// use a0kzg::{Kzg, VerkleTree};
+    /// let mut tree = VerkleTree::new(&Kzg::trusted_setup(256)).unwrap();
+    /// let empty_root = tree.root_commitment();
+    ///
+    /// tree.insert([1; 32], [2; 32]);
+    /// assert_eq!(tree.get(&[1; 32]), Some([2; 32]));
+    /// assert_ne!(tree.root_commitment(), empty_root);
+    ///
+    /// assert_eq!(tree.delete(&[1; 32]), Some([2; 32]));
+    /// assert_eq!(tree.root_commitment(), empty_root);
+    /// ```
+    pub fn new(kzg: &Kzg) -> Result<Self, KzgError> {
+        let lagrange_g1 = lagrange_basis(kzg)?;
+        Ok(Self::with_committer(Kzg {
+            pow_tau_g1: kzg.pow_tau_g1[..VERKLE_NODE_WIDTH].to_vec(),
+            pow_tau_g2: kzg.pow_tau_g2.iter().take(2).copied().collect(),
+            lagrange_g1,
+            pow_gamma_tau_g1: Vec::new(),
+        }))
+    }
+}
+
+impl VerkleTree<Ipa> {
+    /// Creates an empty tree with the nodes committed with Pedersen commitments over
+    /// the 256 first points of `pedersen_basis`
+    /// # Example
+    /// ```
+    /// use a0kzg::{BandersnatchPoint, VerkleTree};
+    /// let mut tree = VerkleTree::new_ipa();
+    /// assert_eq!(tree.root_commitment(), BandersnatchPoint::identity());
+    /// tree.insert([1; 32], [2; 32]);
+    /// assert_ne!(tree.root_commitment(), BandersnatchPoint::identity());
+    /// ```
+    pub fn new_ipa() -> Self {
+        Self::with_committer(Ipa::new(VERKLE_NODE_WIDTH))
+    }
+}
+
+impl<C: VerkleCommitter> VerkleTree<C> {
+    fn with_committer(committer: C) -> Self {
+        Self {
+            committer,
+            store: MemoryStore::default(),
+            updated: BTreeSet::new(),
+            commitments: RefCell::new(BTreeMap::new()),
+            suffix_commitments: RefCell::new(BTreeMap::new()),
+        }
+    }
+}
+
+impl<C: VerkleCommitter, S: VerkleStore> VerkleTree<C, S> {
+    /// Attaches the tree to `store`, so it has the nodes stored there, for example
+    /// the ones of a `FileStore` committed in a previous session. Only the setup of
+    /// the tree is kept, its current nodes are dropped and not written to `store`, so
+    /// it is meant to be called on a new tree
+    /// # Example
+    /// ```
+    /// use a0kzg::{FileStore, VerkleTree};
+    /// let path = std::env::temp_dir().join(format!("a0kzg-tree-{}.log", std::process::id()));
+    /// let mut tree = VerkleTree::new_ipa().with_store(FileStore::open(&path).unwrap());
+    /// tree.insert([1; 32], [2; 32]);
+    /// tree.commit().unwrap();
+    /// let root = tree.root_commitment();
+    ///
+    /// let tree = VerkleTree::new_ipa().with_store(FileStore::open(&path).unwrap());
+    /// assert_eq!(tree.get(&[1; 32]), Some([2; 32]));
+    /// assert_eq!(tree.root_commitment(), root);
+    /// # std::fs::remove_file(&path).unwrap();
+    /// ```
+    pub fn with_store<T: VerkleStore>(self, store: T) -> VerkleTree<C, T> {
+        VerkleTree {
+            committer: self.committer,
+            store,
+            updated: BTreeSet::new(),
+            commitments: RefCell::new(BTreeMap::new()),
+            suffix_commitments: RefCell::new(BTreeMap::new()),
+        }
+    }
+
+    /// Returns the value of `key`
+    pub fn get(&self, key: &VerkleKey) -> Option<VerkleValue> {
+        let (stem, suffix) = split_key(key);
+        let mut path = Vec::new();
+        loop {
+            match self.node(&path)? {
+                VerkleNode::Internal(children) => {
+                    let index = stem[path.len()];
+                    if !children.contains(&index) {
+                        return None;
+                    }
+                    path.push(index);
+                }
+                VerkleNode::Leaf { stem: s, values } if s == stem => {
+                    return values.get(&suffix).copied()
+                }
+                VerkleNode::Leaf { .. } => return None,
+            }
+        }
+    }
+
+    /// Sets the value of `key`, returning the previous one
+    pub fn insert(&mut self, key: VerkleKey, value: VerkleValue) -> Option<VerkleValue> {
+        let (stem, suffix) = split_key(&key);
+        self.suffix_commitments
+            .get_mut()
+            .remove(&(stem, suffix / 128));
+        let mut path = Vec::new();
+        loop {
+            match self.node(&path).expect("the path leads to a node") {
+                VerkleNode::Internal(mut children) => {
+                    let index = stem[path.len()];
+                    if children.insert(index) {
+                        self.put(&path, Some(VerkleNode::Internal(children)));
+                        path.push(index);
+                        let values = BTreeMap::from([(suffix, value)]);
+                        self.put(&path, Some(VerkleNode::Leaf { stem, values }));
+                        return None;
+                    }
+                    path.push(index);
+                }
+                VerkleNode::Leaf {
+                    stem: s,
+                    mut values,
+                } if s == stem => {
+                    let previous = values.insert(suffix, value);
+                    self.put(&path, Some(VerkleNode::Leaf { stem, values }));
+                    return previous;
+                }
+                VerkleNode::Leaf { stem: s, values } => {
+                    // the leaf is pushed down into a new internal node, that is split
+                    // again in the next iteration if the stems still share a byte
+                    let index = s[path.len()];
+                    let mut leaf_path = path.clone();
+                    leaf_path.push(index);
+                    self.put(&leaf_path, Some(VerkleNode::Leaf { stem: s, values }));
+                    let children = BTreeSet::from([index]);
+                    self.put(&path, Some(VerkleNode::Internal(children)));
+                }
+            }
+        }
+    }
+
+    /// Removes `key`, returning its value
+    pub fn delete(&mut self, key: &VerkleKey) -> Option<VerkleValue> {
+        let (stem, suffix) = split_key(key);
+        self.delete_at(&[], &stem, suffix)
+    }
+
+    /// Makes the updates durable, as a single batch of writes to the store with the
+    /// commitments of the updated nodes, so they are not computed again when the
+    /// store is opened again. The commitments cached in memory are dropped
+    pub fn commit(&mut self) -> io::Result<()> {
+        let commitments: Vec<(Vec<u8>, Vec<u8>)> = self
+            .updated
+            .iter()
+            .filter(|path| self.store.get(path).is_some())
+            .map(|path| {
+                (
+                    path.clone(),
+                    C::commitment_to_bytes(&self.commit_node(path)),
+                )
+            })
+            .collect();
+        for (path, commitment) in commitments {
+            self.store.put_commitment(&path, commitment);
+        }
+        self.store.commit()?;
+        self.updated.clear();
+        self.commitments.get_mut().clear();
+        self.suffix_commitments.get_mut().clear();
+        Ok(())
+    }
+
+    /// Returns the commitment of the root node
+    pub fn root_commitment(&self) -> C::Commitment {
+        self.commit_node(&[])
+    }
+
+    /// Returns the node at `path`, the root is always an internal node
+    pub(crate) fn node(&self, path: &[u8]) -> Option<VerkleNode> {
+        match self.store.get(path) {
+            None if path.is_empty() => Some(VerkleNode::Internal(BTreeSet::new())),
+            node => node,
+        }
+    }
+
+    /// Writes the node at `path`, dropping the commitments that depend on it
+    fn put(&mut self, path: &[u8], node: Option<VerkleNode>) {
+        let commitments = self.commitments.get_mut();
+        for depth in 0..=path.len() {
+            commitments.remove(&path[..depth]);
+            self.updated.insert(path[..depth].to_vec());
+        }
+        self.store.put(path, node);
+    }
+
+    /// Removes the value and collapses the internal nodes that are left with a single
+    /// leaf, so the tree has the same shape as if the key was never inserted
+    fn delete_at(&mut self, path: &[u8], stem: &Stem, suffix: u8) -> Option<VerkleValue> {
+        let mut children = match self.node(path)? {
+            VerkleNode::Internal(children) => children,
+            VerkleNode::Leaf {
+                stem: s,
+                mut values,
+            } if s == *stem => {
+                let value = values.remove(&suffix)?;
+                self.suffix_commitments.get_mut().remove(&(s, suffix / 128));
+                self.put(path, Some(VerkleNode::Leaf { stem: s, values }));
+                return Some(value);
+            }
+            VerkleNode::Leaf { .. } => return None,
+        };
+        let index = stem[path.len()];
+        if !children.contains(&index) {
+            return None;
+        }
+        let child_path = [path, &[index]].concat();
+        let value = self.delete_at(&child_path, stem, suffix)?;
+
+        match self.store.get(&child_path) {
+            Some(VerkleNode::Leaf { values, .. }) if values.is_empty() => {
+                let suffix_commitments = self.suffix_commitments.get_mut();
+                suffix_commitments.remove(&(*stem, 0));
+                suffix_commitments.remove(&(*stem, 1));
+            }
+            Some(VerkleNode::Internal(grandchildren)) if grandchildren.is_empty() => {}
+            Some(VerkleNode::Internal(grandchildren)) if grandchildren.len() == 1 => {
+                let grandchild_path = [&child_path[..], &[*grandchildren.first()?]].concat();
+                if let Some(leaf @ VerkleNode::Leaf { .. }) = self.store.get(&grandchild_path) {
+                    self.put(&grandchild_path, None);
+                    self.put(&child_path, Some(leaf));
+                }
+                return Some(value);
+            }
+            _ => return Some(value),
+        }
+        self.put(&child_path, None);
+        children.remove(&index);
+        self.put(path, Some(VerkleNode::Internal(children)));
+        Some(value)
+    }
+
+    /// Returns the evaluations over the domain of the polinomial committed by the
+    /// node at `path`
+    pub(crate) fn node_evals(&self, path: &[u8]) -> Vec<C::Scalar> {
+        let mut evals = vec![C::scalar_from_le_bytes(&[]); VERKLE_NODE_WIDTH];
+        match self.node(path).expect("the path leads to a node") {
+            VerkleNode::Internal(children) => {
+                for index in children.iter() {
+                    let child = self.commit_node(&[path, &[*index]].concat());
+                    evals[*index as usize] = C::commitment_to_field(&child);
+                }
+            }
+            VerkleNode::Leaf { stem, values } => {
+                let (c1, c2) = (
+                    self.commit_suffixes(&stem, &values, 0),
+                    self.commit_suffixes(&stem, &values, 1),
+                );
+                evals[0] = C::scalar_from_le_bytes(&[1]);
+                evals[1] = stem_to_field::<C>(&stem);
+                evals[2] = C::commitment_to_field(&c1);
+                evals[3] = C::commitment_to_field(&c2);
+            }
+        }
+        evals
+    }
+
+    /// Returns the evaluations of the polinomial of the `half` (0 for C1 and 1 for C2)
+    /// of the values of a leaf
+    pub(crate) fn suffix_evals(values: &BTreeMap<u8, VerkleValue>, half: usize) -> Vec<C::Scalar> {
+        let mut evals = vec![C::scalar_from_le_bytes(&[]); VERKLE_NODE_WIDTH];
+        let range = (128 * half) as u8..=(128 * half + 127) as u8;
+        for (suffix, value) in values.range(range) {
+            let i = 2 * (*suffix as usize % 128);
+            let (low, high) = value_to_fields::<C>(value);
+            evals[i] = low;
+            evals[i + 1] = high;
+        }
+        evals
+    }
+
+    /// Returns the commitment of the node at `path`, only computed if it was updated
+    /// since the last time, or if it is not stored with the node
+    pub(crate) fn commit_node(&self, path: &[u8]) -> C::Commitment {
+        if let Some(commitment) = self.commitments.borrow().get(path) {
+            return *commitment;
+        }
+        if !self.updated.contains(path) {
+            let stored = self.store.get_commitment(path);
+            if let Some(commitment) = stored.and_then(|c| C::commitment_from_bytes(&c)) {
+                return commitment;
+            }
+        }
+        let commitment = self.commit_evals(&self.node_evals(path));
+        self.commitments
+            .borrow_mut()
+            .insert(path.to_vec(), commitment);
+        commitment
+    }
+
+    pub(crate) fn commit_suffixes(
+        &self,
+        stem: &Stem,
+        values: &BTreeMap<u8, VerkleValue>,
+        half: usize,
+    ) -> C::Commitment {
+        let key = (*stem, half as u8);
+        if let Some(commitment) = self.suffix_commitments.borrow().get(&key) {
+            return *commitment;
+        }
+        let commitment = self.commit_evals(&Self::suffix_evals(values, half));
+        self.suffix_commitments.borrow_mut().insert(key, commitment);
+        commitment
+    }
+
+    pub(crate) fn commit_evals(&self, evals: &[C::Scalar]) -> C::Commitment {
+        self.committer.commit_evals(evals)
+    }
+}
+
+/// Computes [L_i(tau)]₁ for the domain of size 256 from the first powers of tau
+fn lagrange_basis(kzg: &Kzg) -> Result<Vec<G1Affine>, KzgError> {
+    if kzg.pow_tau_g1.len() < VERKLE_NODE_WIDTH {
+        return Err(KzgError::DegreeTooLarge {
+            degree: VERKLE_NODE_WIDTH - 1,
+            max: kzg.pow_tau_g1.len().saturating_sub(1),
+        });
+    }
+    let mut basis: Vec<G1Projective> = kzg.pow_tau_g1[..VERKLE_NODE_WIDTH]
+        .iter()
+        .map(G1Projective::from)
+        .collect();
+    EvaluationDomain::new(VERKLE_NODE_WIDTH)
+        .unwrap()
+        .ifft_g1(&mut basis);
+    let mut basis_affine = vec![G1Affine::identity(); VERKLE_NODE_WIDTH];
+    G1Projective::batch_normalize(&basis, &mut basis_affine);
+    Ok(basis_affine)
+}
+
+pub(crate) fn split_key(key: &VerkleKey) -> (Stem, u8) {
+    let mut stem = [0u8; 31];
+    stem.copy_from_slice(&key[..31]);
+    (stem, key[31])
+}
+
+/// Maps a commitment to a field element to be used as a child of its parent, the
+/// identity (an empty node) maps to zero
+pub(crate) fn commitment_to_field(c: &Commitment) -> Scalar {
+    if bool::from(c.is_identity()) {
+        return Scalar::zero();
+    }
+    let mut bytes = [0u8; 64];
+    bytes[..32].copy_from_slice(&Sha256::digest(G1Affine::from(c).to_compressed()));
+    Scalar::from_bytes_wide(&bytes)
+}
+
+/// The stem as a little-endian field element, 31 bytes always fit
+pub(crate) fn stem_to_field<C: VerkleCommitter>(stem: &Stem) -> C::Scalar {
+    C::scalar_from_le_bytes(stem)
+}
+
+/// Splits the value in the low and high 16 bytes as little-endian field elements,
+/// the low one is marked with 2^128 so a zero value is different from a missing one
+pub(crate) fn value_to_fields<C: VerkleCommitter>(value: &VerkleValue) -> (C::Scalar, C::Scalar) {
+    let mut low = [0u8; 17];
+    low[..16].copy_from_slice(&value[..16]);
+    low[16] = 1;
+    (
+        C::scalar_from_le_bytes(&low),
+        C::scalar_from_le_bytes(&value[16..]),
+    )
+}
+
+#[cfg(test)]
+pub(crate) fn test_kzg() -> &'static Kzg {
+    use once_cell::sync::Lazy;
+    static KZG: Lazy<Kzg> = Lazy::new(|| Kzg::trusted_setup(VERKLE_NODE_WIDTH));
+    &KZG
+}
+
+#[cfg(test)]
+pub(crate) fn test_keys() -> Vec<VerkleKey> {
+    let mut keys = Vec::new();
+    for i in 0..6u8 {
+        // keys with the same stem, with stems sharing a prefix and with unrelated stems
+        let mut key = [i.wrapping_mul(37); 32];
+        keys.push(key);
+        key[31] = 200;
+        keys.push(key);
+        key[5] ^= 1;
+        keys.push(key);
+        key[30] ^= 1;
+        keys.push(key);
+    }
+    keys
+}
+
+#[test]
+fn test_verkle_insert_get() {
+    assert!(VerkleTree::new(&Kzg::trusted_setup(255)).is_err());
+
+    let mut tree = VerkleTree::new(test_kzg()).unwrap();
+    let keys = test_keys();
+    for (i, key) in keys.iter().enumerate() {
+        assert_eq!(tree.insert(*key, [i as u8; 32]), None);
+    }
+    for (i, key) in keys.iter().enumerate() {
+        assert_eq!(tree.get(key), Some([i as u8; 32]));
+    }
+    let mut missing = keys[0];
+    missing[31] = 1;
+    assert_eq!(tree.get(&missing), None);
+    missing[10] = 1;
+    assert_eq!(tree.get(&missing), None);
+
+    assert_eq!(tree.insert(keys[3], [99; 32]), Some([3; 32]));
+    assert_eq!(tree.get(&keys[3]), Some([99; 32]));
+}
+
+#[test]
+fn test_verkle_root_commitment() {
+    let keys = test_keys();
+    let mut tree = VerkleTree::new(test_kzg()).unwrap();
+    assert_eq!(tree.root_commitment(), G1Projective::identity());
+
+    for (i, key) in keys.iter().enumerate() {
+        tree.insert(*key, [i as u8; 32]);
+    }
+    let root = tree.root_commitment();
+
+    // the root does not depend on the insertion order
+    let mut reversed = VerkleTree::new(test_kzg()).unwrap();
+    for (i, key) in keys.iter().enumerate().rev() {
+        reversed.insert(*key, [i as u8; 32]);
+    }
+    assert_eq!(reversed.root_commitment(), root);
+
+    // a zero value is different from a missing one
+    let mut zero = VerkleTree::new(test_kzg()).unwrap();
+    zero.insert(keys[0], [0; 32]);
+    assert_ne!(zero.root_commitment(), G1Projective::identity());
+
+    // deleting gives the same root than never inserting
+    let mut partial = VerkleTree::new(test_kzg()).unwrap();
+    for (i, key) in keys.iter().enumerate().skip(2) {
+        partial.insert(*key, [i as u8; 32]);
+    }
+    assert_eq!(tree.delete(&keys[0]), Some([0; 32]));
+    assert_eq!(tree.delete(&keys[0]), None);
+    assert_eq!(tree.delete(&keys[1]), Some([1; 32]));
+    assert_eq!(tree.root_commitment(), partial.root_commitment());
+
+    for key in keys.iter().skip(2) {
+        assert!(tree.delete(key).is_some());
+    }
+    assert_eq!(tree.root_commitment(), G1Projective::identity());
+}
+
+#[test]
+fn test_verkle_node_commitment() {
+    let mut tree = VerkleTree::new(test_kzg()).unwrap();
+    tree.insert([7; 32], [1; 32]);
+    let mut key = [7; 32];
+    key[31] = 130;
+    tree.insert(key, [2; 32]);
+
+    // a single leaf below the root, that commits to [1, stem, C1, C2]
+    let values: BTreeMap<u8, VerkleValue> =
+        vec![(7, [1; 32]), (130, [2; 32])].into_iter().collect();
+    let domain = EvaluationDomain::new(VERKLE_NODE_WIDTH).unwrap();
+    let commit = |evals: &[Scalar]| test_kzg().commit(&domain.ifft(evals)).unwrap();
+    let c1 = commit(&VerkleTree::<Kzg>::suffix_evals(&values, 0));
+    let c2 = commit(&VerkleTree::<Kzg>::suffix_evals(&values, 1));
+    let mut leaf = vec![Scalar::zero(); VERKLE_NODE_WIDTH];
+    leaf[0] = Scalar::one();
+    leaf[1] = stem_to_field::<Kzg>(&[7; 31]);
+    leaf[2] = commitment_to_field(&c1);
+    leaf[3] = commitment_to_field(&c2);
+    let mut root = vec![Scalar::zero(); VERKLE_NODE_WIDTH];
+    root[7] = commitment_to_field(&commit(&leaf));
+    assert_eq!(tree.root_commitment(), commit(&root));
+}
+
+#[test]
+fn test_verkle_ipa_commitment() {
+    let mut tree = VerkleTree::new_ipa();
+    tree.insert([7; 32], [1; 32]);
+    let mut key = [7; 32];
+    key[31] = 130;
+    tree.insert(key, [2; 32]);
+
+    // the same layout than with KZG, committed as Σ v_i·G_i, with the bytes read as
+    // little-endian numbers digit by digit
+    let k = BandersnatchScalar::from;
+    let le = |bytes: &[u8]| {
+        bytes
+            .iter()
+            .rev()
+            .fold(k(0), |acc, b| acc * k(256) + k(*b as u64))
+    };
+    let leaf_marker = le(&[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]);
+    assert_eq!(stem_to_field::<Ipa>(&[7; 31]), le(&[7; 31]));
+    assert_eq!(
+        value_to_fields::<Ipa>(&[1; 32]),
+        (le(&[1; 16]) + leaf_marker, le(&[1; 16]))
+    );
+
+    let basis = super::bandersnatch::pedersen_basis(VERKLE_NODE_WIDTH);
+    let commit = |evals: &[BandersnatchScalar]| msm_bandersnatch(&basis, evals);
+    let (mut c1, mut c2) = (vec![k(0); VERKLE_NODE_WIDTH], vec![k(0); VERKLE_NODE_WIDTH]);
+    c1[14] = le(&[1; 16]) + leaf_marker;
+    c1[15] = le(&[1; 16]);
+    c2[4] = le(&[2; 16]) + leaf_marker;
+    c2[5] = le(&[2; 16]);
+    let mut leaf = vec![k(0); VERKLE_NODE_WIDTH];
+    leaf[0] = k(1);
+    leaf[1] = le(&[7; 31]);
+    leaf[2] = commit(&c1).map_to_field();
+    leaf[3] = commit(&c2).map_to_field();
+    let mut root = vec![BandersnatchScalar::from(0); VERKLE_NODE_WIDTH];
+    root[7] = commit(&leaf).map_to_field();
+    assert_eq!(tree.root_commitment(), commit(&root));
+
+    // and also independent of the insertion order
+    let keys = test_keys();
+    let (mut tree, mut reversed) = (VerkleTree::new_ipa(), VerkleTree::new_ipa());
+    for (i, key) in keys.iter().enumerate() {
+        tree.insert(*key, [i as u8; 32]);
+    }
+    for (i, key) in keys.iter().enumerate().rev() {
+        reversed.insert(*key, [i as u8; 32]);
+    }
+    assert_eq!(tree.root_commitment(), reversed.root_commitment());
+    for key in keys.iter() {
+        tree.delete(key);
+    }
+    assert_eq!(tree.root_commitment(), BandersnatchPoint::identity());
+}
+
+/// A cheap linear commitment, Σ (i+1)·v_i, that counts the vectors it commits
+#[cfg(test)]
+#[derive(Default)]
+struct CountingCommitter(std::cell::Cell<usize>);
+
+#[cfg(test)]
+impl VerkleCommitter for CountingCommitter {
+    type Scalar = Scalar;
+    type Commitment = Scalar;
+
+    fn scalar_from_le_bytes(bytes: &[u8]) -> Scalar {
+        Kzg::scalar_from_le_bytes(bytes)
+    }
+
+    fn commit_evals(&self, evals: &[Scalar]) -> Scalar {
+        self.0.set(self.0.get() + 1);
+        evals
+            .iter()
+            .zip(1u64..)
+            .fold(Scalar::zero(), |acc, (v, i)| acc + v * Scalar::from(i))
+    }
+
+    fn commitment_to_field(c: &Scalar) -> Scalar {
+        *c
+    }
+
+    fn commitment_to_bytes(c: &Scalar) -> Vec<u8> {
+        c.to_bytes().to_vec()
+    }
+
+    fn commitment_from_bytes(bytes: &[u8]) -> Option<Scalar> {
+        Scalar::from_bytes(bytes.try_into().ok()?).into()
+    }
+}
+
+#[test]
+fn test_verkle_cached_commitments() {
+    let keys = test_keys();
+    let mut tree = VerkleTree::with_committer(CountingCommitter::default());
+    for (i, key) in keys.iter().enumerate() {
+        tree.insert(*key, [i as u8; 32]);
+    }
+    let root = tree.root_commitment();
+    tree.committer.0.set(0);
+    assert_eq!(tree.root_commitment(), root);
+    assert_eq!(tree.committer.0.get(), 0);
+
+    // an update only commits again its C1 or C2, its leaf and the nodes above it
+    tree.insert(keys[2], [99; 32]);
+    let (stem, _) = split_key(&keys[2]);
+    let depth = (1..=31)
+        .find(|d| matches!(tree.store.get(&stem[..*d]), Some(VerkleNode::Leaf { .. })))
+        .unwrap();
+    let root = tree.root_commitment();
+    assert_eq!(tree.committer.0.get(), depth + 2);
+
+    // and gives the same commitments than computing all of them again
+    let uncached = |tree: &VerkleTree<CountingCommitter>| {
+        let mut uncached = VerkleTree::with_committer(CountingCommitter::default());
+        uncached.store = tree.store.clone();
+        uncached.root_commitment()
+    };
+    assert_eq!(root, uncached(&tree));
+    for key in keys.iter().rev() {
+        tree.delete(key);
+        assert_eq!(tree.root_commitment(), uncached(&tree));
+    }
+    assert_eq!(tree.root_commitment(), Scalar::zero());
+    for (i, key) in keys.iter().enumerate() {
+        tree.insert(*key, [i as u8; 32]);
+        assert_eq!(tree.root_commitment(), uncached(&tree));
+    }
+}
+
+#[test]
+fn test_verkle_file_store() {
+    use super::verkle_store::{temp_log, FileStore};
+
+    let path = temp_log("tree");
+    let keys = test_keys();
+    let mut tree = VerkleTree::with_committer(CountingCommitter::default())
+        .with_store(FileStore::open(&path).unwrap());
+    let mut memory = VerkleTree::with_committer(CountingCommitter::default());
+    for (i, key) in keys.iter().enumerate() {
+        tree.insert(*key, [i as u8; 32]);
+        memory.insert(*key, [i as u8; 32]);
+    }
+    tree.commit().unwrap();
+    for key in keys.iter().take(5) {
+        tree.delete(key);
+        memory.delete(key);
+    }
+    tree.commit().unwrap();
+    assert!(tree.commitments.borrow().is_empty());
+    // not committed, so lost when the log is opened again
+    tree.insert(keys[0], [1; 32]);
+    drop(tree);
+
+    // the commitments are stored with the nodes, so none is computed again
+    let mut tree = VerkleTree::with_committer(CountingCommitter::default())
+        .with_store(FileStore::open(&path).unwrap());
+    assert_eq!(tree.root_commitment(), memory.root_commitment());
+    assert_eq!(tree.committer.0.get(), 0);
+    for key in keys.iter() {
+        assert_eq!(tree.get(key), memory.get(key));
+    }
+
+    // but the ones of an update, its leaf with C1 and C2 and the nodes above it
+    tree.insert(keys[7], [1; 32]);
+    memory.insert(keys[7], [1; 32]);
+    let (stem, _) = split_key(&keys[7]);
+    let depth = (1..=31)
+        .find(|d| matches!(tree.store.get(&stem[..*d]), Some(VerkleNode::Leaf { .. })))
+        .unwrap();
+    assert_eq!(tree.root_commitment(), memory.root_commitment());
+    assert_eq!(tree.committer.0.get(), depth + 3);
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,511 @@
+//! This module provides proofs of inclusion and absence of many keys of a Verkle
+//! tree, with a single multiproof for all the openings of all the commitments along
+//! the paths of the keys
+//! see https://dankradfeist.de/ethereum/2021/06/18/pcs-multiproofs.html
+//!
+//! For each key the proof opens each internal node of its path at the index of the
+//! next byte of the stem. The path ends in:
+//! - an empty child, opened to zero, so the key is absent. The node is also opened
+//!   at 0 to its first child, that is not the leaf marker, so it is not a leaf
+//! - the leaf of other stem, opened at 0 (leaf marker) and 1 (the other stem), so
+//!   the key is absent
+//! - the leaf of the stem, opened at 0, 1 and at the C1 or C2 commitment of the
+//!   suffix, that is opened at the two evaluations of the value, that are zero if
+//!   the value is absent
+//!
+//! All the openings are grouped by commitment and proved with `Kzg::open_multi`
+
+use super::{
+    domain::EvaluationDomain,
+    kzg::{Commitment, Kzg, KzgError},
+    poly::Poly,
+    shplonk::MultiProof,
+    transcript::Sha256Transcript,
+    verkle::{
+        commitment_to_field, split_key, stem_to_field, value_to_fields, Stem, VerkleKey,
+        VerkleTree, VerkleValue, VERKLE_NODE_WIDTH,
+    },
+    verkle_store::{VerkleNode, VerkleStore},
+};
+use bls12_381::Scalar;
+use std::collections::BTreeMap;
+
+const TRANSCRIPT_DOMAIN: &[u8] = b"a0kzg-verkle-multiproof";
+
+/// How the path of a stem ends
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExtensionStatus {
+    /// The internal node at `depth` has no child for the stem
+    Empty { depth: u8 },
+    /// The leaf at `depth` belongs to other stem, that shares the first depth bytes
+    OtherStem { depth: u8, stem: Stem },
+    /// The leaf of the stem is at `depth`
+    Present { depth: u8 },
+}
+
+/// A proof for the values of many keys of a Verkle tree
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VerkleProof {
+    /// How the path ends, for each key
+    pub extensions: Vec<ExtensionStatus>,
+    /// Commitments of the nodes in the paths, by the path from the root
+    pub commitments: BTreeMap<Vec<u8>, Commitment>,
+    /// C1 (0) and C2 (1) commitments of the leaves where a suffix is opened
+    pub suffix_commitments: BTreeMap<(Stem, u8), Commitment>,
+    /// Proof of all the openings
+    pub multiproof: MultiProof,
+}
+
+/// A committed polinomial of the tree
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum PolyId {
+    /// The node at the path
+    Node(Vec<u8>),
+    /// The C1 (0) or C2 (1) polinomial of the leaf of the stem
+    Suffix(Stem, u8),
+}
+
+/// The openings of a committed polinomial, the index of the domain and the value
+struct Openings {
+    commitment: Commitment,
+    points: BTreeMap<u8, Scalar>,
+}
+
+impl Openings {
+    /// Adds an opening, failing if the index was already opened to other value
+    fn open(&mut self, index: usize, value: Scalar) -> Option<()> {
+        let index = index as u8;
+        match self.points.insert(index, value) {
+            Some(previous) if previous != value => None,
+            _ => Some(()),
+        }
+    }
+}
+
+impl<S: VerkleStore> VerkleTree<Kzg, S> {
+    /// Proves the values of `keys`, that can be present or absent. Fails if the
+    /// setup of the tree can not open its polinomials
+    ///
+    /// # Panics
+    /// If a node of the tree is missing in the store, that is only expected if the
+    /// store was modified outside of the tree
+    /// # Example
+    /// ```
+    /// use a0kzg::{verify_verkle_proof, Kzg, VerkleTree};
+    /// let kzg = Kzg::trusted_setup(256);
+    /// let mut tree = VerkleTree::new(&kzg).unwrap();
+    /// tree.insert([1; 32], [2; 32]);
+    /// tree.insert([3; 32], [4; 32]);
+    ///
+    /// let keys = [[1; 32], [5; 32]];
+    /// let proof = tree.prove(&keys).unwrap();
+    /// let root = tree.root_commitment();
+    /// assert!(verify_verkle_proof(&kzg, &root, &keys, &[Some([2; 32]), None], &proof));
+    /// assert!(!verify_verkle_proof(&kzg, &root, &keys, &[Some([2; 32]), Some([0; 32])], &proof));
+    /// ```
+    pub fn prove(&self, keys: &[VerkleKey]) -> Result<VerkleProof, KzgError> {
+        let mut extensions = Vec::with_capacity(keys.len());
+        let mut commitments = BTreeMap::new();
+        let mut suffix_commitments = BTreeMap::new();
+        // the evaluations and openings of each committed polinomial
+        let mut polys: BTreeMap<PolyId, (Vec<Scalar>, Openings)> = BTreeMap::new();
+        let mut open = |id: PolyId, commit: &dyn Fn() -> (Commitment, Vec<Scalar>), index| {
+            let (evals, openings) = polys.entry(id).or_insert_with(|| {
+                let (commitment, evals) = commit();
+                let openings = Openings {
+                    commitment,
+                    points: BTreeMap::new(),
+                };
+                (evals, openings)
+            });
+            openings.open(index, evals[index]);
+        };
+
+        let node_poly = |path: &[u8]| (self.commit_node(path), self.node_evals(path));
+        for key in keys.iter() {
+            let (stem, suffix) = split_key(key);
+            let mut path = Vec::new();
+            loop {
+                let id = PolyId::Node(path.clone());
+                match self.node(&path).expect("the path leads to a node") {
+                    VerkleNode::Internal(children) => {
+                        let index = stem[path.len()];
+                        open(id.clone(), &|| node_poly(&path), index as usize);
+                        if !children.contains(&index) {
+                            open(id, &|| node_poly(&path), 0);
+                            if children.contains(&0) {
+                                let first = [&path[..], &[0]].concat();
+                                commitments.insert(first.clone(), self.commit_node(&first));
+                            }
+                            extensions.push(ExtensionStatus::Empty {
+                                depth: path.len() as u8,
+                            });
+                            break;
+                        }
+                        path.push(index);
+                        commitments.insert(path.clone(), self.commit_node(&path));
+                    }
+                    VerkleNode::Leaf { stem: s, values } => {
+                        open(id.clone(), &|| node_poly(&path), 0);
+                        open(id.clone(), &|| node_poly(&path), 1);
+                        let depth = path.len() as u8;
+                        if s != stem {
+                            extensions.push(ExtensionStatus::OtherStem { depth, stem: s });
+                            break;
+                        }
+                        let half = suffix / 128;
+                        open(id, &|| node_poly(&path), 2 + half as usize);
+                        let suffix_poly = || {
+                            let commitment = self.commit_suffixes(&stem, &values, half as usize);
+                            (commitment, Self::suffix_evals(&values, half as usize))
+                        };
+                        let i = 2 * (suffix as usize % 128);
+                        open(PolyId::Suffix(stem, half), &suffix_poly, i);
+                        open(PolyId::Suffix(stem, half), &suffix_poly, i + 1);
+                        suffix_commitments.insert((stem, half), suffix_poly().0);
+                        extensions.push(ExtensionStatus::Present { depth });
+                        break;
+                    }
+                }
+            }
+        }
+
+        let multiproof = open_polys(&self.committer, &self.root_commitment(), &polys)?;
+
+        Ok(VerkleProof {
+            extensions,
+            commitments,
+            suffix_commitments,
+            multiproof,
+        })
+    }
+}
+
+/// Proves all the openings of the committed `polys` of the tree with the `root`
+/// commitment, given with their evaluations
+fn open_polys(
+    kzg: &Kzg,
+    root: &Commitment,
+    polys: &BTreeMap<PolyId, (Vec<Scalar>, Openings)>,
+) -> Result<MultiProof, KzgError> {
+    let domain = EvaluationDomain::new(VERKLE_NODE_WIDTH).unwrap();
+    let mut transcript = Sha256Transcript::new(TRANSCRIPT_DOMAIN);
+    transcript.append_commitment(b"root", root);
+    let coeffs: Vec<Poly> = polys
+        .values()
+        .map(|(evals, _)| domain.ifft(evals))
+        .collect();
+    let points: Vec<Vec<(Scalar, Scalar)>> = polys
+        .values()
+        .map(|(_, openings)| {
+            transcript.append_commitment(b"commitment", &openings.commitment);
+            to_points(&domain, &openings.points)
+        })
+        .collect();
+    let queries: Vec<(&Poly, &[(Scalar, Scalar)])> = coeffs
+        .iter()
+        .zip(points.iter())
+        .map(|(p, s)| (p, s.as_slice()))
+        .collect();
+    kzg.open_multi(&queries, &mut transcript)
+}
+
+/// Verifies that `keys` have `values` (None for the absent ones) in the tree with
+/// the `root` commitment
+pub fn verify_verkle_proof(
+    kzg: &Kzg,
+    root: &Commitment,
+    keys: &[VerkleKey],
+    values: &[Option<VerkleValue>],
+    proof: &VerkleProof,
+) -> bool {
+    if keys.len() != values.len() || keys.len() != proof.extensions.len() {
+        return false;
+    }
+    let openings = match expected_openings(root, keys, values, proof) {
+        Some(openings) => openings,
+        None => return false,
+    };
+
+    let domain = EvaluationDomain::new(VERKLE_NODE_WIDTH).unwrap();
+    let mut transcript = Sha256Transcript::new(TRANSCRIPT_DOMAIN);
+    transcript.append_commitment(b"root", root);
+    let points: Vec<Vec<(Scalar, Scalar)>> = openings
+        .values()
+        .map(|openings| {
+            transcript.append_commitment(b"commitment", &openings.commitment);
+            to_points(&domain, &openings.points)
+        })
+        .collect();
+    let queries: Vec<(Commitment, &[(Scalar, Scalar)])> = openings
+        .values()
+        .zip(points.iter())
+        .map(|(openings, s)| (openings.commitment, s.as_slice()))
+        .collect();
+    kzg.verify_multi(&queries, &proof.multiproof, &mut transcript)
+}
+
+/// Computes the openings that the proof should prove for the keys to have the
+/// values, None if the proof is not consistent with them
+fn expected_openings(
+    root: &Commitment,
+    keys: &[VerkleKey],
+    values: &[Option<VerkleValue>],
+    proof: &VerkleProof,
+) -> Option<BTreeMap<PolyId, Openings>> {
+    let mut openings: BTreeMap<PolyId, Openings> = BTreeMap::new();
+    let mut open = |id: PolyId, commitment: Commitment, index: usize, value: Scalar| {
+        openings
+            .entry(id)
+            .or_insert_with(|| Openings {
+                commitment,
+                points: BTreeMap::new(),
+            })
+            .open(index, value)
+    };
+    let commitment_at = |path: &[u8]| {
+        if path.is_empty() {
+            Some(*root)
+        } else {
+            proof.commitments.get(path).copied()
+        }
+    };
+
+    // the nodes in the paths are never empty
+    if proof
+        .commitments
+        .values()
+        .any(|c| bool::from(c.is_identity()))
+    {
+        return None;
+    }
+
+    for ((key, value), extension) in keys.iter().zip(values).zip(proof.extensions.iter()) {
+        let (stem, suffix) = split_key(key);
+        let depth = match extension {
+            ExtensionStatus::Empty { depth } => *depth as usize,
+            ExtensionStatus::OtherStem { depth, .. } | ExtensionStatus::Present { depth } => {
+                *depth as usize
+            }
+        };
+        if depth >= stem.len() {
+            return None;
+        }
+
+        // internal nodes from the root, each one opened at the next node
+        for d in 0..depth {
+            let child = commitment_field(commitment_at(&stem[..d + 1])?);
+            open(
+                PolyId::Node(stem[..d].to_vec()),
+                commitment_at(&stem[..d])?,
+                stem[d] as usize,
+                child,
+            )?;
+        }
+
+        let id = PolyId::Node(stem[..depth].to_vec());
+        let commitment = commitment_at(&stem[..depth])?;
+        match extension {
+            ExtensionStatus::Empty { .. } => {
+                if value.is_some() {
+                    return None;
+                }
+                // the node is internal, as its first child is not the leaf marker
+                let first = proof
+                    .commitments
+                    .get(&[&stem[..depth], &[0]].concat())
+                    .map_or(Scalar::zero(), |c| commitment_field(*c));
+                if first == Scalar::one() {
+                    return None;
+                }
+                open(id.clone(), commitment, 0, first)?;
+                open(id, commitment, stem[depth] as usize, Scalar::zero())?;
+            }
+            ExtensionStatus::OtherStem {
+                stem: other_stem, ..
+            } => {
+                if depth == 0
+                    || value.is_some()
+                    || *other_stem == stem
+                    || other_stem[..depth] != stem[..depth]
+                {
+                    return None;
+                }
+                open(id.clone(), commitment, 0, Scalar::one())?;
+                open(id, commitment, 1, stem_to_field::<Kzg>(other_stem))?;
+            }
+            ExtensionStatus::Present { .. } => {
+                if depth == 0 {
+                    return None;
+                }
+                let half = suffix / 128;
+                let suffix_commitment = *proof.suffix_commitments.get(&(stem, half))?;
+                open(id.clone(), commitment, 0, Scalar::one())?;
+                open(id.clone(), commitment, 1, stem_to_field::<Kzg>(&stem))?;
+                open(
+                    id,
+                    commitment,
+                    2 + half as usize,
+                    commitment_field(suffix_commitment),
+                )?;
+
+                let (low, high) = match value {
+                    Some(value) => value_to_fields::<Kzg>(value),
+                    None => (Scalar::zero(), Scalar::zero()),
+                };
+                let id = PolyId::Suffix(stem, half);
+                let i = 2 * (suffix as usize % 128);
+                open(id.clone(), suffix_commitment, i, low)?;
+                open(id, suffix_commitment, i + 1, high)?;
+            }
+        }
+    }
+    Some(openings)
+}
+
+fn commitment_field(c: Commitment) -> Scalar {
+    commitment_to_field(&c)
+}
+
+/// The opened points as (w^index, value)
+fn to_points(domain: &EvaluationDomain, points: &BTreeMap<u8, Scalar>) -> Vec<(Scalar, Scalar)> {
+    points
+        .iter()
+        .map(|(index, value)| (domain.element(*index as usize), *value))
+        .collect()
+}
+
+#[test]
+fn test_verkle_proof() {
+    use super::verkle::{test_keys, test_kzg};
+
+    let kzg = test_kzg();
+    let mut tree = VerkleTree::new(kzg).unwrap();
+    let keys = test_keys();
+    for (i, key) in keys.iter().enumerate().skip(4) {
+        tree.insert(*key, [i as u8; 32]);
+    }
+    let root = tree.root_commitment();
+
+    // present keys, absent keys in an empty slot, in a leaf of other stem and in a
+    // present stem
+    let mut other_suffix = keys[4];
+    other_suffix[31] = 3;
+    let mut other_stem = keys[4];
+    other_stem[30] ^= 0x80;
+    let proved = vec![keys[4], keys[9], keys[0], other_stem, other_suffix, keys[4]];
+    let values = vec![
+        Some([4; 32]),
+        Some([9; 32]),
+        None,
+        None,
+        None,
+        Some([4; 32]),
+    ];
+    let proof = tree.prove(&proved).unwrap();
+    assert!(matches!(
+        proof.extensions[..5],
+        [
+            ExtensionStatus::Present { .. },
+            ExtensionStatus::Present { .. },
+            ExtensionStatus::Empty { .. },
+            ExtensionStatus::OtherStem { .. },
+            ExtensionStatus::Present { .. },
+        ]
+    ));
+    assert!(verify_verkle_proof(kzg, &root, &proved, &values, &proof));
+
+    // changing any value, the root or the proof fails
+    for i in 0..values.len() {
+        let mut bad = values.clone();
+        bad[i] = match bad[i] {
+            Some(_) => None,
+            None => Some([0; 32]),
+        };
+        assert!(!verify_verkle_proof(kzg, &root, &proved, &bad, &proof));
+    }
+    let mut bad = values.clone();
+    bad[1] = Some([8; 32]);
+    assert!(!verify_verkle_proof(kzg, &root, &proved, &bad, &proof));
+
+    assert!(!verify_verkle_proof(
+        kzg,
+        &tree.commit_evals(&[Scalar::one()]),
+        &proved,
+        &values,
+        &proof
+    ));
+    let mut bad_proof = proof.clone();
+    bad_proof.extensions[2] = ExtensionStatus::Empty { depth: 2 };
+    assert!(!verify_verkle_proof(
+        kzg, &root, &proved, &values, &bad_proof
+    ));
+    let mut bad_proof = proof.clone();
+    let path = bad_proof.commitments.keys().next().unwrap().clone();
+    bad_proof.commitments.insert(path, root);
+    assert!(!verify_verkle_proof(
+        kzg, &root, &proved, &values, &bad_proof
+    ));
+    assert!(!verify_verkle_proof(
+        kzg,
+        &root,
+        &proved[..5],
+        &values[..5],
+        &proof
+    ));
+
+    // an absence proof can not end in a leaf or in an empty node of a present key
+    let mut tree = VerkleTree::new(kzg).unwrap();
+    tree.insert([5; 32], [2; 32]);
+    tree.insert([0; 32], [3; 32]);
+    let root = tree.root_commitment();
+    let proof = tree.prove(&[[7; 32]]).unwrap();
+    assert!(proof.commitments.contains_key(&vec![0]));
+    assert!(verify_verkle_proof(kzg, &root, &[[7; 32]], &[None], &proof));
+    let leaf = tree.commit_node(&[5]);
+    let empty = tree.commit_evals(&[]);
+    let forge = |extension, commitments: Vec<(Vec<u8>, Commitment)>| {
+        let mut polys = BTreeMap::new();
+        for (path, commitment) in [(vec![], root)].iter().chain(commitments.iter()) {
+            let evals = match tree.node(path) {
+                Some(_) => tree.node_evals(path),
+                None => vec![Scalar::zero(); VERKLE_NODE_WIDTH],
+            };
+            let mut openings = Openings {
+                commitment: *commitment,
+                points: BTreeMap::new(),
+            };
+            openings.open(5, evals[5]).unwrap();
+            polys.insert(PolyId::Node(path.clone()), (evals, openings));
+        }
+        VerkleProof {
+            extensions: vec![extension],
+            commitments: commitments.into_iter().collect(),
+            suffix_commitments: BTreeMap::new(),
+            multiproof: open_polys(&tree.committer, &root, &polys).unwrap(),
+        }
+    };
+    let forged = [
+        forge(ExtensionStatus::Empty { depth: 1 }, vec![(vec![5], leaf)]),
+        forge(
+            ExtensionStatus::Empty { depth: 2 },
+            vec![(vec![5], leaf), (vec![5, 5], empty)],
+        ),
+    ];
+    assert_eq!(tree.get(&[5; 32]), Some([2; 32]));
+    for proof in forged.iter() {
+        assert!(!verify_verkle_proof(kzg, &root, &[[5; 32]], &[None], proof));
+    }
+
+    // the empty tree proves absence at the root
+    let empty = VerkleTree::new(kzg).unwrap();
+    let proof = empty.prove(&keys[..2]).unwrap();
+    assert_eq!(proof.extensions[0], ExtensionStatus::Empty { depth: 0 });
+    assert!(verify_verkle_proof(
+        kzg,
+        &empty.root_commitment(),
+        &keys[..2],
+        &[None, None],
+        &proof
+    ));
+}
@@ -0,0 +1,595 @@
+//! This module implements the storage of the nodes of a `VerkleTree`, each node is
+//! stored by its path from the root (the first bytes of the stems below it), so the
+//! tree only loads the nodes along the paths it walks. The commitment of each node is
+//! stored with it, so the tree does not compute again the ones of the nodes that were
+//! not updated.
+//!
+//! The writes are kept pending until `VerkleStore::commit`, that applies them all at
+//! once. `FileStore` appends each committed batch to a log file followed by its
+//! SHA-256, when the file is opened the batches are replayed to index the last
+//! version of each node, and a batch that was not fully written is discarded.
+
+use super::verkle::{Stem, VerkleValue};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    convert::{TryFrom, TryInto},
+    fs::{File, OpenOptions},
+    io::{self, BufReader, Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+/// A node of a Verkle tree as stored
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum VerkleNode {
+    /// An internal node, with the indexes of its children
+    Internal(BTreeSet<u8>),
+    /// A leaf (extension) node, with the values of its stem by suffix
+    Leaf {
+        stem: Stem,
+        values: BTreeMap<u8, VerkleValue>,
+    },
+}
+
+const INTERNAL_TAG: u8 = 0;
+const LEAF_TAG: u8 = 1;
+
+impl VerkleNode {
+    /// Encodes an internal node as its tag and the bitmap of its children, and a
+    /// leaf as its tag, the stem and each suffix followed by its value
+    fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            VerkleNode::Internal(children) => {
+                let mut bytes = vec![0u8; 33];
+                bytes[0] = INTERNAL_TAG;
+                for index in children.iter() {
+                    bytes[1 + *index as usize / 8] |= 1 << (index % 8);
+                }
+                bytes
+            }
+            VerkleNode::Leaf { stem, values } => {
+                let mut bytes = Vec::with_capacity(32 + 33 * values.len());
+                bytes.push(LEAF_TAG);
+                bytes.extend_from_slice(stem);
+                for (suffix, value) in values.iter() {
+                    bytes.push(*suffix);
+                    bytes.extend_from_slice(value);
+                }
+                bytes
+            }
+        }
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        match bytes.split_first()? {
+            (&INTERNAL_TAG, bitmap) if bitmap.len() == 32 => Some(VerkleNode::Internal(
+                (0..=255u8)
+                    .filter(|i| bitmap[*i as usize / 8] & (1 << (i % 8)) != 0)
+                    .collect(),
+            )),
+            (&LEAF_TAG, rest) if rest.len() >= 31 && (rest.len() - 31) % 33 == 0 => {
+                let (stem, entries) = rest.split_at(31);
+                Some(VerkleNode::Leaf {
+                    stem: stem.try_into().unwrap(),
+                    values: entries
+                        .chunks(33)
+                        .map(|entry| (entry[0], entry[1..].try_into().unwrap()))
+                        .collect(),
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// The storage of the nodes of a `VerkleTree`
+pub trait VerkleStore {
+    /// Returns the node at `path`, including the writes not committed yet
+    fn get(&self, path: &[u8]) -> Option<VerkleNode>;
+    /// Sets the node at `path`, or removes it if None, until the next commit. The
+    /// commitment stored with the previous node is dropped
+    fn put(&mut self, path: &[u8], node: Option<VerkleNode>);
+    /// Returns the encoded commitment stored with the node at `path`
+    fn get_commitment(&self, path: &[u8]) -> Option<Vec<u8>>;
+    /// Stores the encoded `commitment` with the node at `path` until the next commit,
+    /// nothing is stored if there is no node
+    fn put_commitment(&mut self, path: &[u8], commitment: Vec<u8>);
+    /// Applies all the pending writes as a single batch
+    fn commit(&mut self) -> io::Result<()>;
+}
+
+/// A node with its encoded commitment, if stored
+type StoredNode = (VerkleNode, Option<Vec<u8>>);
+
+/// A store that keeps the nodes in memory, the writes are applied immediately
+#[derive(Clone, Debug, Default)]
+pub struct MemoryStore {
+    nodes: BTreeMap<Vec<u8>, StoredNode>,
+}
+
+impl VerkleStore for MemoryStore {
+    fn get(&self, path: &[u8]) -> Option<VerkleNode> {
+        self.nodes.get(path).map(|(node, _)| node.clone())
+    }
+
+    fn put(&mut self, path: &[u8], node: Option<VerkleNode>) {
+        match node {
+            Some(node) => self.nodes.insert(path.to_vec(), (node, None)),
+            None => self.nodes.remove(path),
+        };
+    }
+
+    fn get_commitment(&self, path: &[u8]) -> Option<Vec<u8>> {
+        self.nodes.get(path)?.1.clone()
+    }
+
+    fn put_commitment(&mut self, path: &[u8], commitment: Vec<u8>) {
+        if let Some((_, stored)) = self.nodes.get_mut(path) {
+            *stored = Some(commitment);
+        }
+    }
+
+    fn commit(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A store that appends the committed batches to a log file, and only keeps in
+/// memory where the last version of each node is in the file and the pending writes
+///
+/// Each batch is its length as a little-endian u32 followed by its bitwise
+/// complement, the records and their SHA-256. Only a batch that reaches the end of
+/// the log may be incomplete, it is discarded when the log is opened. Each record is
+/// the length of the path as u8, the path, the length of the encoded node as a
+/// little-endian u32 (zero if removed) and the encoded node, and if not removed the
+/// length of the encoded commitment as u8 (zero if not stored) and the commitment.
+#[derive(Debug)]
+pub struct FileStore {
+    file: File,
+    /// Length of the log with all the batches committed
+    len: u64,
+    /// Offset and length of the last encoding of each node in the log, and the
+    /// length of its commitment, that follows the node and its length
+    index: BTreeMap<Vec<u8>, (u64, usize, usize)>,
+    pending: BTreeMap<Vec<u8>, Option<StoredNode>>,
+}
+
+impl FileStore {
+    /// Opens the log at `path`, creating it if it does not exist
+    /// # Example
+    /// ```
+    /// use a0kzg::{FileStore, VerkleNode, VerkleStore};
+    /// let path = std::env::temp_dir().join(format!("a0kzg-doc-{}.log", std::process::id()));
+    /// let mut store = FileStore::open(&path).unwrap();
+    /// let node = VerkleNode::Internal(vec![1, 2].into_iter().collect());
+    /// store.put(&[], Some(node.clone()));
+    /// store.commit().unwrap();
+    ///
+    /// let store = FileStore::open(&path).unwrap();
+    /// assert_eq!(store.get(&[]), Some(node));
+    /// # std::fs::remove_file(&path).unwrap();
+    /// ```
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)?;
+        let file_len = file.metadata()?.len();
+
+        let mut index = BTreeMap::new();
+        let mut len = 0;
+        let mut reader = BufReader::new(&file);
+        loop {
+            match read_batch(&mut reader, file_len - len)? {
+                Batch::Records(records) => {
+                    index_records(&mut index, len + BATCH_HEADER_LEN, &records)
+                        .ok_or_else(|| invalid_data("invalid verkle store record"))?;
+                    len += BATCH_HEADER_LEN + records.len() as u64 + 32;
+                }
+                // the last batch was being written when the log was closed
+                Batch::Torn => {
+                    file.set_len(len)?;
+                    break;
+                }
+                Batch::End => break,
+            }
+        }
+
+        Ok(Self {
+            file,
+            len,
+            index,
+            pending: BTreeMap::new(),
+        })
+    }
+
+    fn read_node(&self, offset: u64, len: usize) -> io::Result<VerkleNode> {
+        VerkleNode::from_bytes(&self.read(offset, len)?)
+            .ok_or_else(|| invalid_data("invalid verkle node"))
+    }
+
+    fn read(&self, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+        let mut file = &self.file;
+        let mut bytes = vec![0u8; len];
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_exact(&mut bytes)?;
+        Ok(bytes)
+    }
+}
+
+impl VerkleStore for FileStore {
+    /// Returns the node at `path`
+    ///
+    /// # Panics
+    /// If the log can not be read, that is only expected if it was modified after
+    /// being opened
+    fn get(&self, path: &[u8]) -> Option<VerkleNode> {
+        if let Some(entry) = self.pending.get(path) {
+            return entry.as_ref().map(|(node, _)| node.clone());
+        }
+        let (offset, len, _) = *self.index.get(path)?;
+        Some(
+            self.read_node(offset, len)
+                .expect("verkle store log is readable"),
+        )
+    }
+
+    fn put(&mut self, path: &[u8], node: Option<VerkleNode>) {
+        self.pending
+            .insert(path.to_vec(), node.map(|node| (node, None)));
+    }
+
+    /// Returns the commitment stored with the node at `path`
+    ///
+    /// # Panics
+    /// If the log can not be read, that is only expected if it was modified after
+    /// being opened
+    fn get_commitment(&self, path: &[u8]) -> Option<Vec<u8>> {
+        if let Some(entry) = self.pending.get(path) {
+            return entry.as_ref()?.1.clone();
+        }
+        let (offset, len, commitment_len) = *self.index.get(path)?;
+        if commitment_len == 0 {
+            return None;
+        }
+        Some(
+            self.read(offset + len as u64 + 1, commitment_len)
+                .expect("verkle store log is readable"),
+        )
+    }
+
+    /// Stores the commitment with the node at `path`, that is written again if it
+    /// is not pending
+    ///
+    /// # Panics
+    /// If the log can not be read, that is only expected if it was modified after
+    /// being opened
+    fn put_commitment(&mut self, path: &[u8], commitment: Vec<u8>) {
+        match self.pending.get_mut(path) {
+            Some(Some((_, stored))) => *stored = Some(commitment),
+            Some(None) => {}
+            None => {
+                if let Some(node) = self.get(path) {
+                    self.pending
+                        .insert(path.to_vec(), Some((node, Some(commitment))));
+                }
+            }
+        }
+    }
+
+    fn commit(&mut self) -> io::Result<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        let mut records = Vec::new();
+        let mut written = Vec::with_capacity(self.pending.len());
+        for (path, entry) in self.pending.iter() {
+            let (node, commitment) = match entry {
+                Some((node, commitment)) => (node.to_bytes(), commitment.as_deref()),
+                None => (Vec::new(), None),
+            };
+            let commitment = commitment.unwrap_or_default();
+            if commitment.len() > u8::MAX as usize {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "verkle store commitment is too large",
+                ));
+            }
+            records.push(path.len() as u8);
+            records.extend_from_slice(path);
+            records.extend_from_slice(&(node.len() as u32).to_le_bytes());
+            written.push((
+                path,
+                self.len + BATCH_HEADER_LEN + records.len() as u64,
+                node.len(),
+                commitment.len(),
+            ));
+            records.extend_from_slice(&node);
+            if !node.is_empty() {
+                records.push(commitment.len() as u8);
+                records.extend_from_slice(commitment);
+            }
+        }
+        let records_len = batch_records_len(records.len())?;
+        let mut batch = Vec::with_capacity(BATCH_HEADER_LEN as usize + records.len() + 32);
+        batch.extend_from_slice(&records_len.to_le_bytes());
+        batch.extend_from_slice(&(!records_len).to_le_bytes());
+        batch.extend_from_slice(&records);
+        batch.extend_from_slice(&Sha256::digest(&records));
+
+        if let Err(err) = self
+            .file
+            .write_all(&batch)
+            .and_then(|_| self.file.sync_data())
+        {
+            // drop what was written of the batch, so the next ones can follow
+            let _ = self.file.set_len(self.len);
+            return Err(err);
+        }
+
+        for (path, offset, len, commitment_len) in written {
+            if len == 0 {
+                self.index.remove(path);
+            } else {
+                self.index
+                    .insert(path.clone(), (offset, len, commitment_len));
+            }
+        }
+        self.len += batch.len() as u64;
+        self.pending.clear();
+        Ok(())
+    }
+}
+
+/// Length of the batch header, the length of the records and its complement
+const BATCH_HEADER_LEN: u64 = 8;
+
+/// What follows a batch boundary of the log
+enum Batch {
+    /// The records of a complete batch
+    Records(Vec<u8>),
+    /// A batch that was not completely written, and reaches the end of the log
+    Torn,
+    /// The end of the log
+    End,
+}
+
+/// Reads the next batch of the `remaining` bytes of the log. Fails if the batch is
+/// corrupted and it is not the last one, since then the log was not just torn
+fn read_batch<R: Read>(reader: &mut R, remaining: u64) -> io::Result<Batch> {
+    if remaining == 0 {
+        return Ok(Batch::End);
+    }
+    if remaining < BATCH_HEADER_LEN {
+        return Ok(Batch::Torn);
+    }
+    let mut header = [0u8; BATCH_HEADER_LEN as usize];
+    reader.read_exact(&mut header)?;
+    let len = u32::from_le_bytes(header[..4].try_into().unwrap());
+    let batch_len = BATCH_HEADER_LEN + len as u64 + 32;
+    if !len != u32::from_le_bytes(header[4..].try_into().unwrap()) {
+        // the header was not completely written, or the log was extended with zeros
+        // but the batch was never written
+        if batch_len >= remaining || is_zero(reader, remaining - BATCH_HEADER_LEN)? {
+            return Ok(Batch::Torn);
+        }
+        return Err(invalid_data("corrupted verkle store batch length"));
+    }
+    if batch_len > remaining {
+        return Ok(Batch::Torn);
+    }
+    let mut records = vec![0u8; len as usize];
+    let mut hash = [0u8; 32];
+    reader.read_exact(&mut records)?;
+    reader.read_exact(&mut hash)?;
+    if Sha256::digest(&records).as_slice() != hash {
+        return if batch_len == remaining {
+            Ok(Batch::Torn)
+        } else {
+            Err(invalid_data("corrupted verkle store batch"))
+        };
+    }
+    Ok(Batch::Records(records))
+}
+
+/// Returns if the next `len` bytes are all zero
+fn is_zero<R: Read>(reader: &mut R, len: u64) -> io::Result<bool> {
+    let mut buf = [0u8; 4096];
+    let mut left = len;
+    while left > 0 {
+        let n = left.min(buf.len() as u64) as usize;
+        reader.read_exact(&mut buf[..n])?;
+        if buf[..n].iter().any(|b| *b != 0) {
+            return Ok(false);
+        }
+        left -= n as u64;
+    }
+    Ok(true)
+}
+
+/// Returns the length of the records of a batch as written in its header, fails if
+/// it does not fit, so the pending writes have to be committed in smaller batches
+fn batch_records_len(len: usize) -> io::Result<u32> {
+    u32::try_from(len).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "verkle store batch is too large",
+        )
+    })
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Indexes the nodes of the `records` of a batch that starts at `offset` of the log,
+/// None if they are not well formed
+fn index_records(
+    index: &mut BTreeMap<Vec<u8>, (u64, usize, usize)>,
+    offset: u64,
+    records: &[u8],
+) -> Option<()> {
+    let mut pos = 0;
+    while pos < records.len() {
+        let path_len = *records.get(pos)? as usize;
+        let path = records.get(pos + 1..pos + 1 + path_len)?;
+        pos += 1 + path_len;
+        let len = u32::from_le_bytes(records.get(pos..pos + 4)?.try_into().unwrap()) as usize;
+        pos += 4;
+        if len == 0 {
+            index.remove(path);
+            continue;
+        }
+        VerkleNode::from_bytes(records.get(pos..pos + len)?)?;
+        let commitment_len = *records.get(pos + len)? as usize;
+        records.get(pos + len + 1..pos + len + 1 + commitment_len)?;
+        index.insert(path.to_vec(), (offset + pos as u64, len, commitment_len));
+        pos += len + 1 + commitment_len;
+    }
+    Some(())
+}
+
+#[cfg(test)]
+pub(crate) fn temp_log(name: &str) -> std::path::PathBuf {
+    let path = std::env::temp_dir().join(format!("a0kzg-{}-{}.log", name, std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+#[test]
+fn test_verkle_node_bytes() {
+    let internal = VerkleNode::Internal(vec![0, 7, 8, 255].into_iter().collect());
+    let leaf = VerkleNode::Leaf {
+        stem: [3; 31],
+        values: vec![(0, [1; 32]), (200, [2; 32])].into_iter().collect(),
+    };
+    for node in [internal, leaf] {
+        assert_eq!(VerkleNode::from_bytes(&node.to_bytes()), Some(node));
+    }
+    assert_eq!(VerkleNode::from_bytes(&[]), None);
+    assert_eq!(VerkleNode::from_bytes(&[INTERNAL_TAG; 32]), None);
+    assert_eq!(VerkleNode::from_bytes(&[LEAF_TAG; 33]), None);
+}
+
+#[test]
+fn test_batch_records_len() {
+    assert_eq!(batch_records_len(0).unwrap(), 0);
+    assert_eq!(batch_records_len(u32::MAX as usize).unwrap(), u32::MAX);
+    if let Some(len) = (u32::MAX as usize).checked_add(1) {
+        let err = batch_records_len(len).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+}
+
+#[test]
+fn test_file_store() {
+    let path = temp_log("store");
+    let leaf = |v| VerkleNode::Leaf {
+        stem: [1; 31],
+        values: vec![(5, [v; 32])].into_iter().collect(),
+    };
+    let root = VerkleNode::Internal(BTreeSet::from([1]));
+
+    let mut store = FileStore::open(&path).unwrap();
+    store.put(&[], Some(root.clone()));
+    store.put(&[1], Some(leaf(1)));
+    assert_eq!(store.get(&[1]), Some(leaf(1)));
+    store.commit().unwrap();
+    store.put(&[1], Some(leaf(2)));
+    store.put(&[2], Some(leaf(3)));
+    store.commit().unwrap();
+    store.put(&[2], None);
+    store.commit().unwrap();
+    assert_eq!(store.get(&[1]), Some(leaf(2)));
+    assert_eq!(store.get(&[2]), None);
+
+    // the pending writes are lost if not committed
+    store.put(&[1], Some(leaf(4)));
+    drop(store);
+    let store = FileStore::open(&path).unwrap();
+    assert_eq!(store.get(&[]), Some(root.clone()));
+    assert_eq!(store.get(&[1]), Some(leaf(2)));
+    assert_eq!(store.get(&[2]), None);
+    drop(store);
+
+    // a partially written batch is discarded, and the log can still be appended
+    let len = std::fs::metadata(&path).unwrap().len();
+    let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+    file.write_all(&[100, 0, 0, 0, 1, 2, 3]).unwrap();
+    drop(file);
+    let mut store = FileStore::open(&path).unwrap();
+    assert_eq!(std::fs::metadata(&path).unwrap().len(), len);
+    store.put(&[3], Some(leaf(5)));
+    store.commit().unwrap();
+    let store = FileStore::open(&path).unwrap();
+    assert_eq!(store.get(&[1]), Some(leaf(2)));
+    assert_eq!(store.get(&[3]), Some(leaf(5)));
+    drop(store);
+
+    // so is a complete last batch that does not match its hash
+    let log = std::fs::read(&path).unwrap();
+    let last = log.len() - 1;
+    let mut torn = log.clone();
+    torn[last] ^= 1;
+    std::fs::write(&path, &torn).unwrap();
+    let store = FileStore::open(&path).unwrap();
+    assert_eq!(store.get(&[1]), Some(leaf(2)));
+    assert_eq!(store.get(&[3]), None);
+    drop(store);
+
+    // and a tail of zeros, from a log that was extended but not written
+    for zeros in [16, 4096] {
+        let mut zeroed = log.clone();
+        zeroed.resize(log.len() + zeros, 0);
+        std::fs::write(&path, &zeroed).unwrap();
+        let store = FileStore::open(&path).unwrap();
+        assert_eq!(store.get(&[3]), Some(leaf(5)));
+        assert_eq!(std::fs::read(&path).unwrap(), log);
+    }
+
+    // but a corrupted batch followed by others is an error, and the log is kept
+    for pos in [0, 9] {
+        let mut corrupted = log.clone();
+        corrupted[pos] ^= 1;
+        std::fs::write(&path, &corrupted).unwrap();
+        let err = FileStore::open(&path).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(std::fs::read(&path).unwrap(), corrupted);
+    }
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_store_commitments() {
+    let path = temp_log("commitments");
+    let root = VerkleNode::Internal(BTreeSet::from([1]));
+    let mut memory = MemoryStore::default();
+    let mut file = FileStore::open(&path).unwrap();
+    let stores: [&mut dyn VerkleStore; 2] = [&mut memory, &mut file];
+    for store in stores {
+        store.put_commitment(&[], vec![1; 48]);
+        assert_eq!(store.get_commitment(&[]), None);
+        store.put(&[], Some(root.clone()));
+        store.put_commitment(&[], vec![1; 48]);
+        assert_eq!(store.get_commitment(&[]), Some(vec![1; 48]));
+        store.commit().unwrap();
+        assert_eq!(store.get_commitment(&[]), Some(vec![1; 48]));
+        store.put(&[], Some(root.clone()));
+        assert_eq!(store.get_commitment(&[]), None);
+        store.commit().unwrap();
+    }
+    drop(file);
+
+    // the commitment of a committed node is written again with it
+    let mut store = FileStore::open(&path).unwrap();
+    assert_eq!(store.get_commitment(&[]), None);
+    store.put_commitment(&[], vec![2; 32]);
+    store.commit().unwrap();
+    let store = FileStore::open(&path).unwrap();
+    assert_eq!(store.get(&[]), Some(root));
+    assert_eq!(store.get_commitment(&[]), Some(vec![2; 32]));
+
+    std::fs::remove_file(&path).unwrap();
+}
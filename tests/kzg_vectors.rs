@@ -0,0 +1,304 @@
+//! Runs the `kzg-mainnet` test vectors of the Ethereum consensus specs against the
+//! EIP-4844 API, using the ceremony trusted setup.
+//! see https://github.com/ethereum/consensus-specs/tree/dev/tests/formats/kzg_4844
+//!
+//! Each case lives in `tests/vectors/kzg-mainnet/<handler>/<case>/data.yaml`, an
+//! `output` of null means that the inputs are invalid and the call must fail. The
+//! invalid input cases of the prover handlers live in `kzg-mainnet-derived`, see
+//! `tests/vectors/README.md`.
+//!
+//! The correct proof cases are generated with `blob_to_kzg_commitment`,
+//! `compute_kzg_proof` and `compute_blob_kzg_proof`, so the prover is checked to
+//! give byte for byte the same commitments, evaluations and proofs.
+
+use a0kzg::{Blob, Bytes48, Kzg, BYTES_PER_BLOB};
+use bls12_381::{G1Affine, G2Affine};
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+use std::{
+    collections::BTreeMap,
+    convert::TryInto,
+    fmt::Debug,
+    fs,
+    path::{Path, PathBuf},
+};
+
+static KZG: Lazy<Kzg> = Lazy::new(|| {
+    Kzg::load_trusted_setup_txt(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/src/trusted_setup.txt"
+    ))
+    .expect("cannot load trusted setup")
+});
+
+#[derive(Deserialize)]
+struct TestCase<I, O = bool> {
+    input: I,
+    output: Option<O>,
+}
+
+#[derive(Deserialize)]
+struct BlobToKzgCommitmentInput {
+    blob: String,
+}
+
+#[derive(Deserialize)]
+struct ComputeKzgProofInput {
+    blob: String,
+    z: String,
+}
+
+#[derive(Deserialize)]
+struct ComputeBlobKzgProofInput {
+    blob: String,
+    commitment: String,
+}
+
+#[derive(Deserialize)]
+struct VerifyKzgProofInput {
+    commitment: String,
+    z: String,
+    y: String,
+    proof: String,
+}
+
+#[derive(Deserialize)]
+struct VerifyBlobKzgProofInput {
+    blob: String,
+    commitment: String,
+    proof: String,
+}
+
+#[derive(Deserialize)]
+struct VerifyBlobKzgProofBatchInput {
+    blobs: Vec<String>,
+    commitments: Vec<String>,
+    proofs: Vec<String>,
+}
+
+/// Returns the `data.yaml` of all the cases of `handler`, sorted by name
+fn cases(handler: &str) -> Vec<PathBuf> {
+    cases_in("kzg-mainnet", handler)
+}
+
+/// Returns the `data.yaml` of all the cases of `handler` in the `suite` directory
+fn cases_in(suite: &str, handler: &str) -> Vec<PathBuf> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/vectors")
+        .join(suite)
+        .join(handler);
+    let mut cases: Vec<PathBuf> = fs::read_dir(&dir)
+        .unwrap_or_else(|e| panic!("cannot read {}: {}", dir.display(), e))
+        .map(|entry| entry.unwrap().path().join("data.yaml"))
+        .collect();
+    cases.sort();
+    assert!(!cases.is_empty(), "no cases in {}", dir.display());
+    cases
+}
+
+fn load<I, O>(path: &Path) -> TestCase<I, O>
+where
+    I: for<'de> Deserialize<'de>,
+    O: for<'de> Deserialize<'de>,
+{
+    let data = fs::read_to_string(path).unwrap();
+    serde_yaml::from_str(&data).unwrap_or_else(|e| panic!("{}: {}", path.display(), e))
+}
+
+/// Decodes a 0x-prefixed hex string into a fixed size array, None if the string has
+/// other length, as the spec considers this an invalid input
+fn bytes<const N: usize>(s: &str) -> Option<[u8; N]> {
+    hex::decode(s.trim_start_matches("0x"))
+        .ok()?
+        .try_into()
+        .ok()
+}
+
+fn blob(s: &str) -> Option<Box<Blob>> {
+    let v = hex::decode(s.trim_start_matches("0x")).ok()?;
+    if v.len() != BYTES_PER_BLOB {
+        return None;
+    }
+    v.into_boxed_slice().try_into().ok()
+}
+
+/// Encodes bytes as the 0x-prefixed hex strings of the outputs
+fn to_hex(bytes: &[u8]) -> String {
+    format!("0x{}", hex::encode(bytes))
+}
+
+fn check<T: PartialEq + Debug>(path: &Path, result: Option<T>, expected: Option<T>) {
+    assert_eq!(result, expected, "{}", path.display());
+}
+
+/// The blobs of the correct proof cases of the blob handlers by their commitment
+fn correct_blobs() -> BTreeMap<Bytes48, Box<Blob>> {
+    let mut blobs = BTreeMap::new();
+    for path in cases("verify_blob_kzg_proof") {
+        let case: TestCase<VerifyBlobKzgProofInput> = load(&path);
+        if case.output == Some(true) {
+            let commitment = bytes(&case.input.commitment).unwrap();
+            blobs.insert(commitment, blob(&case.input.blob).unwrap());
+        }
+    }
+    blobs
+}
+
+#[test]
+fn test_setup_loads() {
+    assert_eq!(KZG.pow_tau_g1.len(), 4096);
+    assert_eq!(KZG.pow_tau_g2.len(), 65);
+    assert_eq!(KZG.lagrange_g1.len(), 4096);
+
+    // the first points of trusted_setup.txt, L_0 is also the first in bit-reversed order
+    assert_eq!(
+        hex::encode(KZG.lagrange_g1[0].to_compressed()),
+        "a0413c0dcafec6dbc9f47d66785cf1e8c981044f7d13cfe3e4fcbb71b5408dfde6312493cb3c1d30516cb3ca88c03654"
+    );
+    assert_eq!(
+        hex::encode(KZG.pow_tau_g2[1].to_compressed()),
+        "b5bfd7dd8cdeb128843bc287230af38926187075cbfbefa81009a2ce615ac53d2914e5870cb452d2afaaab24f3499f72\
+         185cbfee53492714734429b7b38608e23926c911cceceac9a36851477ba4c60b087041de621000edc98edada20c1def2"
+    );
+    assert_eq!(KZG.pow_tau_g1[0], G1Affine::generator());
+    assert_eq!(KZG.pow_tau_g2[0], G2Affine::generator());
+}
+
+#[test]
+fn test_blob_to_kzg_commitment() {
+    for path in cases_in("kzg-mainnet-derived", "blob_to_kzg_commitment") {
+        let case: TestCase<BlobToKzgCommitmentInput, String> = load(&path);
+        let result = (|| {
+            let blob = blob(&case.input.blob)?;
+            Some(to_hex(&KZG.blob_to_kzg_commitment(&blob).ok()?))
+        })();
+        check(&path, result, case.output);
+    }
+
+    let blobs = correct_blobs();
+    assert!(!blobs.is_empty());
+    for (commitment, blob) in blobs.iter() {
+        assert_eq!(KZG.blob_to_kzg_commitment(blob), Ok(*commitment));
+    }
+}
+
+#[test]
+fn test_compute_kzg_proof() {
+    for path in cases_in("kzg-mainnet-derived", "compute_kzg_proof") {
+        let case: TestCase<ComputeKzgProofInput, Vec<String>> = load(&path);
+        let i = &case.input;
+        let result = (|| {
+            let (blob, z) = (blob(&i.blob)?, bytes(&i.z)?);
+            let (proof, y) = KZG.compute_kzg_proof(&blob, &z).ok()?;
+            Some(vec![to_hex(&proof), to_hex(&y)])
+        })();
+        check(&path, result, case.output);
+    }
+
+    let blobs = correct_blobs();
+    let mut checked = 0;
+    for path in cases("verify_kzg_proof") {
+        let case: TestCase<VerifyKzgProofInput> = load(&path);
+        let i = &case.input;
+        let blob = match bytes(&i.commitment).and_then(|c| blobs.get(&c)) {
+            Some(blob) if case.output == Some(true) => blob,
+            _ => continue,
+        };
+        let z = bytes(&i.z).unwrap();
+        let expected = (bytes(&i.proof).unwrap(), bytes(&i.y).unwrap());
+        assert_eq!(
+            KZG.compute_kzg_proof(blob, &z),
+            Ok(expected),
+            "{}",
+            path.display()
+        );
+        checked += 1;
+    }
+    assert!(checked > 0);
+}
+
+#[test]
+fn test_compute_blob_kzg_proof() {
+    for path in cases_in("kzg-mainnet-derived", "compute_blob_kzg_proof") {
+        let case: TestCase<ComputeBlobKzgProofInput, String> = load(&path);
+        let i = &case.input;
+        let result = (|| {
+            let (blob, commitment) = (blob(&i.blob)?, bytes(&i.commitment)?);
+            Some(to_hex(
+                &KZG.compute_blob_kzg_proof(&blob, &commitment).ok()?,
+            ))
+        })();
+        check(&path, result, case.output);
+    }
+
+    for path in cases("verify_blob_kzg_proof") {
+        let case: TestCase<VerifyBlobKzgProofInput> = load(&path);
+        if case.output != Some(true) {
+            continue;
+        }
+        let i = &case.input;
+        let (blob, commitment) = (blob(&i.blob).unwrap(), bytes(&i.commitment).unwrap());
+        assert_eq!(
+            KZG.compute_blob_kzg_proof(&blob, &commitment),
+            Ok(bytes(&i.proof).unwrap()),
+            "{}",
+            path.display()
+        );
+    }
+}
+
+#[test]
+fn test_verify_kzg_proof() {
+    for path in cases("verify_kzg_proof") {
+        let case: TestCase<VerifyKzgProofInput> = load(&path);
+        let i = &case.input;
+        let result = (|| {
+            let (commitment, z) = (bytes(&i.commitment)?, bytes(&i.z)?);
+            let (y, proof) = (bytes(&i.y)?, bytes(&i.proof)?);
+            KZG.verify_kzg_proof(&commitment, &z, &y, &proof).ok()
+        })();
+        check(&path, result, case.output);
+    }
+}
+
+#[test]
+fn test_verify_blob_kzg_proof() {
+    for path in cases("verify_blob_kzg_proof") {
+        let case: TestCase<VerifyBlobKzgProofInput> = load(&path);
+        let i = &case.input;
+        let result = (|| {
+            let (blob, commitment) = (blob(&i.blob)?, bytes(&i.commitment)?);
+            let proof = bytes(&i.proof)?;
+            KZG.verify_blob_kzg_proof(&blob, &commitment, &proof).ok()
+        })();
+        check(&path, result, case.output);
+    }
+}
+
+#[test]
+fn test_verify_blob_kzg_proof_batch() {
+    for path in cases("verify_blob_kzg_proof_batch") {
+        let case: TestCase<VerifyBlobKzgProofBatchInput> = load(&path);
+        let i = &case.input;
+        let result = (|| {
+            // blobs are filled in place, moving them around overflows the stack
+            let mut blobs = vec![[0u8; BYTES_PER_BLOB]; i.blobs.len()];
+            for (b, s) in blobs.iter_mut().zip(i.blobs.iter()) {
+                b.copy_from_slice(blob(s)?.as_ref());
+            }
+            let commitments = i
+                .commitments
+                .iter()
+                .map(|c| bytes(c))
+                .collect::<Option<Vec<_>>>()?;
+            let proofs = i
+                .proofs
+                .iter()
+                .map(|p| bytes(p))
+                .collect::<Option<Vec<_>>>()?;
+            KZG.verify_blob_kzg_proof_batch(&blobs, &commitments, &proofs)
+                .ok()
+        })();
+        check(&path, result, case.output);
+    }
+}